@@ -0,0 +1,93 @@
+//! Compares the schedules of the reducer on an in-process checker.
+//!
+//! Run with `cargo bench --bench schedule`.
+
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::rc::Rc;
+use std::time::Instant;
+
+use ducere::lower::Lower;
+use ducere::{ReduceRule, Reducer, Schedule};
+
+/// number of functions in the input, each with a needle to keep.
+const FNS: usize = 12;
+
+/// Functions with large nested bodies and a needle at a different depth in
+/// each, so that the work is spread over all levels of the tree instead of
+/// deleting whole items.
+fn input() -> String {
+    let mut s = String::new();
+    for i in 0..FNS {
+        writeln!(s, "fn f{i}(v: &mut Vec<u32>) {{").unwrap();
+        for j in 0..4 {
+            writeln!(s, "for a in 0..{j} {{ v.push(a); if a > {i} {{").unwrap();
+            for k in 0..4 {
+                writeln!(
+                    s,
+                    "while v.len() > {k} {{ let b = v.pop().unwrap() * {k}; v.insert(0, b + a); }}"
+                )
+                .unwrap();
+                if (j, k) == (i % 4, i / 4 % 4) {
+                    writeln!(s, "let needle{i} = {i};").unwrap();
+                }
+            }
+            s.push_str("} }\n");
+        }
+        s.push_str("}\n");
+    }
+    s.push_str("fn main() { let mut v = Vec::new();\n");
+    for i in 0..FNS {
+        writeln!(s, "f{i}(&mut v);").unwrap();
+    }
+    s.push_str("}\n");
+    s
+}
+
+fn run(schedule: Schedule) {
+    let source = input();
+    let file = syn::parse_file(&source).unwrap();
+    // the size of the smallest interesting candidate after each check.
+    let sizes = Rc::new(RefCell::new(Vec::new()));
+    let record = sizes.clone();
+    let original = source.split_whitespace().map(str::len).sum::<usize>();
+
+    let mut reducer = Reducer::new(
+        file.lower(),
+        ReduceRule::Fn(Box::new(move |tmp| {
+            let s = std::fs::read_to_string(tmp.path()).unwrap();
+            let compact: String = s.split_whitespace().collect();
+            let interesting = (0..FNS).all(|i| compact.contains(&format!("letneedle{i}=")))
+                && syn::parse_file(&s).is_ok();
+
+            let mut sizes = record.borrow_mut();
+            let best = sizes.last().copied().unwrap_or(original);
+            sizes.push(if interesting { compact.len() } else { best });
+            interesting
+        })),
+    );
+    reducer.schedule = schedule;
+
+    let start = Instant::now();
+    reducer.reduce().unwrap();
+    let elapsed = start.elapsed();
+
+    // how soon most of the reduction was done, for stopping early.
+    let sizes = sizes.borrow();
+    let last = *sizes.last().unwrap();
+    let most = sizes
+        .iter()
+        .position(|&size| size - last <= (original - last) / 10)
+        .unwrap();
+
+    println!(
+        "{schedule:?}: {} checks, 90% reduced after {most}, {last} bytes left, {elapsed:?}",
+        sizes.len(),
+    );
+}
+
+fn main() {
+    run(Schedule::DepthFirst);
+    run(Schedule::Levels);
+    run(Schedule::LargestFirst);
+}
@@ -1,34 +1,627 @@
+use std::cell::Cell;
 use std::error::Error;
-use std::path::Path;
+use std::ffi::OsString;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process::{self, ExitCode};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use std::{cmp, env, fs};
 
+use ducere::checkpoint::{self, Position};
+use ducere::log;
 use ducere::lower::Lower;
-use ducere::{ReduceRule, Reducer};
+use ducere::oracle::{
+    ClippyOracle, ClippyTarget, DiagnosticOracle, DiagnosticPattern, DifferentialOracle, IceOracle,
+    Miri, Oracle, Predicate, Rustc, RustfmtOracle, RustfmtTarget,
+};
+use ducere::progress::Stats;
+use ducere::{Invocation, ReduceRule, Reducer, Retry};
+use regex::Regex;
 
-fn main() -> Result<(), std::boxed::Box<dyn Error>> {
+const USAGE: &str = "\
+usage: reduceit [options] <file> <checker> [-- <checker args>...]
+       reduceit --ice [options] <file> [<rustc>] [-- <rustc flags>...]
+       reduceit --diagnostic <code> [options] <file> [<rustc>] [-- <rustc flags>...]
+       reduceit --differential <flags> <flags> [options] <file> [<rustc>] [-- <rustc flags>...]
+       reduceit --clippy <lint|crash> [options] <file> [<clippy-driver>] [-- <flags>...]
+       reduceit --rustfmt <crash|unstable|regex> [options] <file> [<rustfmt>] [-- <flags>...]
+
+Reduces <file> while <checker> succeeds. The checker is run in the
+directory of a copy of the file, with its name in place of `{}` in the
+checker args or as the last argument (unless --stdin), and its path in
+$REDUCE_FILE.
+With --ice, reduces <file> while rustc hits the same internal compiler
+error as on the original. With --diagnostic, reduces <file> while rustc
+emits the error. With --differential, reduces <file> while running it
+differs in stdout or exit code between two sets of rustc flags. With
+--clippy and --rustfmt, reduces <file> while the tool still misbehaves.
+
+options:
+    --ice                reduce an internal compiler error of rustc
+    --diagnostic <code>  reduce a diagnostic of rustc, like E0308, or `any`
+    --message <regex>    with --diagnostic, match the message as well, with
+                         --rustfmt crash, match stderr
+    --span <regex>       with --diagnostic, match the primary span's code
+    --clippy <lint>      reduce a lint of clippy like needless_range_loop,
+                         or with `crash`, a crash of clippy
+    --rustfmt <target>   reduce a crash of rustfmt with `crash`, formatting
+                         that changes when formatted again with `unstable`,
+                         or else formatting matching the regex
+    --differential <a> <b>
+                         compare programs compiled with the flags, like
+                         `-Copt-level=0` `-Copt-level=3`. A `+name` flag
+                         selects a toolchain, like `+stable` `+nightly`
+    --miri               with --differential, reject candidates with
+                         undefined behavior according to nightly Miri
+    --timeout <secs>     with --differential, how long programs may run,
+                         defaults to 10
+    --toolchain <name>   the rustup toolchain of rustc, clippy or rustfmt
+    --edition <edition>  the edition, defaults to 2021
+    --crate-type <type>  the crate type for rustc, e.g. `lib`
+    --oracle <predicate> decide on the output of the checker instead of
+                         its exit status, e.g. `ice(/broken MIR/)` or
+                         `stderr(/E0308/) && !signal`. Predicates are
+                         stdout, stderr, output (regexes), exit, signal
+                         and ice, combined with !, && and ||
+    --stdin              pass the copy of the file to the checker's stdin, and
+                         only in place of `{}` in the checker args
+    --file-name <name>   name the copy of the file <name>, like `test.rs`
+    -o <path>            write the reduced file to <path> instead of stdout
+    --report <path>      write statistics per strategy to <path> as JSON
+    --checkpoint <path>  save progress to <path>, defaults to
+                         <file> with the extension `checkpoint.rs`, which
+                         is removed once the reduction finishes
+    --resume             continue from the checkpoint instead of <file>
+    --retry <k>/<n>      run the checker up to <n> times on each candidate,
+                         accepting it if <k> runs succeed, for flaky checkers
+    --verify <secs>      test the reduced file again every <secs> seconds,
+                         rolling back if it is no longer interesting
+    --keep-scratch       keep the directory candidates are checked in
+    --log <dir>          record every candidate, its verdict and the output
+                         of the checker in <dir>
+    --replay <n>         test candidate <n> from the --log directory again
+                         instead of reducing, comparing with the log
+";
+
+/// a single line describing the progress, redrawn in place.
+fn status(stats: &Stats) -> String {
+    let (tried, accepted) = stats
+        .stages
+        .iter()
+        .fold((0, 0), |(t, a), (_, s)| (t + s.tried, a + s.accepted));
+
+    let mut line = format!(
+        "{}/{} tokens ({:.1}%) | round {} {}",
+        stats.tokens,
+        stats.original_tokens,
+        100.0 * stats.tokens as f64 / stats.original_tokens.max(1) as f64,
+        stats.round,
+        stats.stage,
+    );
+    if stats.nodes_total > 0 {
+        line += &format!(" {}/{} nodes", stats.nodes_done, stats.nodes_total);
+    }
+    line += &format!(" | {accepted}/{tried} accepted");
+    if let Some(mean) = stats.checker.mean() {
+        line += &format!(" | checker {:.0?} avg", mean);
+    }
+    if let Some(remaining) = stats.remaining() {
+        line += &format!(" | ~{}s left in stage", remaining.as_secs());
+    }
+    line
+}
+
+/// statistics per stage, printed when the reduction stops.
+fn summary(stats: &Stats) {
+    eprintln!(
+        "{} -> {} tokens in {:.1?}",
+        stats.original_tokens,
+        stats.tokens,
+        stats.started.elapsed()
+    );
+    for (name, stage) in &stats.stages {
+        eprintln!(
+            "  {name:<16} {:>6} tried {:>6} accepted {:>10.1?}",
+            stage.tried, stage.accepted, stage.time
+        );
+    }
+    let c = &stats.checker;
+    if let Some(mean) = c.mean() {
+        eprintln!(
+            "  checker: {} runs, min {:.1?} avg {:.1?} max {:.1?}",
+            c.runs, c.min, mean, c.max
+        );
+    }
+    if stats.flaky > 0 || stats.rollbacks > 0 {
+        eprintln!(
+            "  flaky: {} candidates with differing runs, {} rollbacks",
+            stats.flaky, stats.rollbacks
+        );
+    }
+}
+
+enum Mode {
+    Checker,
+    Ice,
+    Diagnostic,
+    /// the flags of both configurations.
+    Differential(String, String),
+    /// the lint, or `crash`.
+    Clippy(String),
+    Rustfmt(RustfmtTarget),
+}
+
+struct Args {
+    file: PathBuf,
+    checker: Option<PathBuf>,
+    checker_args: Vec<OsString>,
+    oracle: Option<Predicate>,
+    stdin: bool,
+    file_name: Option<OsString>,
+    mode: Mode,
+    /// the diagnostic to keep with `--diagnostic`.
+    pattern: DiagnosticPattern,
+    /// without `program` and `flags`, which are the checker and its args.
+    rustc: Rustc,
+    miri: bool,
+    timeout: Duration,
+    output: Option<PathBuf>,
+    report: Option<PathBuf>,
+    checkpoint: PathBuf,
+    /// whether `checkpoint` was given, to keep it after finishing.
+    keep_checkpoint: bool,
+    resume: bool,
+    retry: Retry,
+    verify: Option<Duration>,
+    keep_scratch: bool,
+    log: Option<PathBuf>,
+    replay: Option<usize>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = env::args_os().skip(1);
+    let mut positional = Vec::new();
+    let mut output = None;
+    let mut report = None;
+    let mut checkpoint = None;
+    let mut resume = false;
+    let mut retry = Retry::default();
+    let mut verify = None;
+    let mut keep_scratch = false;
+    let mut log = None;
+    let mut replay = None;
+    let mut oracle = None;
+    let mut stdin = false;
+    let mut file_name = None;
+    let mut mode = Mode::Checker;
+    let mut pattern = DiagnosticPattern::default();
+    let mut miri = false;
+    let mut timeout = Duration::from_secs(10);
+    let mut rustc = Rustc::default();
+
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--") => break,
+            Some("--oracle") => {
+                let predicate = args.next().ok_or("--oracle needs a predicate")?;
+                let predicate = predicate.to_str().ok_or("the predicate must be UTF-8")?;
+                oracle = Some(
+                    predicate
+                        .parse()
+                        .map_err(|e| format!("invalid predicate: {e}"))?,
+                );
+            }
+            Some("--stdin") => stdin = true,
+            Some("--file-name") => file_name = Some(args.next().ok_or("--file-name needs a name")?),
+            Some("--ice") => mode = Mode::Ice,
+            Some("--diagnostic") => {
+                let code = string(args.next(), "--diagnostic needs an error code")?;
+                pattern.code = (code != "any").then_some(code);
+                mode = Mode::Diagnostic;
+            }
+            Some("--differential") => {
+                let a = string(args.next(), "--differential needs two sets of flags")?;
+                let b = string(args.next(), "--differential needs two sets of flags")?;
+                mode = Mode::Differential(a, b);
+            }
+            Some("--clippy") => {
+                let lint = string(args.next(), "--clippy needs a lint")?;
+                if lint != "crash" {
+                    pattern.code = Some(if lint.contains("::") {
+                        lint.clone()
+                    } else {
+                        format!("clippy::{lint}")
+                    });
+                }
+                mode = Mode::Clippy(lint);
+            }
+            Some("--rustfmt") => {
+                let target = string(args.next(), "--rustfmt needs a target")?;
+                mode = Mode::Rustfmt(match target.as_str() {
+                    "crash" => RustfmtTarget::Crash(None),
+                    "unstable" => RustfmtTarget::Unstable,
+                    re => RustfmtTarget::Output(Regex::new(re).map_err(|e| e.to_string())?),
+                });
+            }
+            Some("--miri") => miri = true,
+            Some("--timeout") => {
+                let secs = string(args.next(), "--timeout needs seconds")?;
+                timeout = Duration::from_secs_f64(
+                    secs.parse()
+                        .map_err(|_| format!("invalid timeout {secs}"))?,
+                );
+            }
+            Some("--message") => {
+                pattern.message = Some(regex(args.next(), "--message needs a regex")?)
+            }
+            Some("--span") => pattern.span_text = Some(regex(args.next(), "--span needs a regex")?),
+            Some("--toolchain") => {
+                rustc.toolchain = Some(string(args.next(), "--toolchain needs a name")?)
+            }
+            Some("--edition") => rustc.edition = string(args.next(), "--edition needs an edition")?,
+            Some("--crate-type") => {
+                rustc.crate_type = Some(string(args.next(), "--crate-type needs a type")?)
+            }
+            Some("-o") => output = Some(args.next().ok_or("-o needs a path")?.into()),
+            Some("--report") => report = Some(args.next().ok_or("--report needs a path")?.into()),
+            Some("--checkpoint") => {
+                checkpoint = Some(args.next().ok_or("--checkpoint needs a path")?.into())
+            }
+            Some("--resume") => resume = true,
+            Some("--retry") => {
+                let policy = string(args.next(), "--retry needs <k>/<n>")?;
+                let invalid = || format!("invalid retry policy {policy}, expected <k>/<n>");
+                let (k, n) = policy.split_once('/').ok_or_else(invalid)?;
+                retry = Retry {
+                    runs: n.parse().map_err(|_| invalid())?,
+                    required: k.parse().map_err(|_| invalid())?,
+                };
+                retry.validate().map_err(|_| invalid())?;
+            }
+            Some("--verify") => {
+                let secs = string(args.next(), "--verify needs seconds")?;
+                verify = Some(Duration::from_secs_f64(
+                    secs.parse()
+                        .map_err(|_| format!("invalid interval {secs}"))?,
+                ));
+            }
+            Some("--keep-scratch") => keep_scratch = true,
+            Some("--log") => log = Some(args.next().ok_or("--log needs a directory")?.into()),
+            Some("--replay") => {
+                let n = string(args.next(), "--replay needs a candidate")?;
+                replay = Some(n.parse().map_err(|_| format!("invalid candidate {n}"))?);
+            }
+            Some("-h" | "--help") => return Err(String::new()),
+            Some(flag) if flag.starts_with('-') => return Err(format!("unknown option {flag}")),
+            _ => positional.push(PathBuf::from(arg)),
+        }
+    }
+
+    let uses_rustc = !matches!(mode, Mode::Checker);
+    let mut positional = positional.into_iter();
+    let (file, checker) = match (positional.next(), positional.next(), positional.next()) {
+        (Some(file), Some(checker), None) => (file, Some(checker)),
+        (Some(file), None, None) if uses_rustc => (file, None),
+        _ if uses_rustc => return Err("expected a file and optionally rustc".to_owned()),
+        _ => return Err("expected a file and a checker".to_owned()),
+    };
+
+    Ok(Args {
+        keep_checkpoint: checkpoint.is_some(),
+        checkpoint: checkpoint.unwrap_or_else(|| file.with_extension("checkpoint.rs")),
+        file,
+        checker,
+        checker_args: args.collect(),
+        oracle,
+        stdin,
+        file_name,
+        mode,
+        pattern,
+        rustc,
+        miri,
+        timeout,
+        output,
+        report,
+        resume,
+        retry,
+        verify,
+        keep_scratch,
+        log,
+        replay,
+    })
+}
+
+/// Test a candidate from the log again, printing how it went compared to
+/// the log. Returns whether the verdict is the same.
+fn replay(reducer: &Reducer, dir: &Path, n: usize) -> Result<bool, Box<dyn Error>> {
+    let entries = log::read(dir)?;
+    let logged = entries
+        .get(n)
+        .ok_or_else(|| format!("the log has {} candidates", entries.len()))?;
+    let replayed = reducer.check(&log::source(dir, logged)?)?;
+
+    let verdict = |e: &log::Entry| {
+        if e.interesting {
+            "interesting"
+        } else {
+            "not interesting"
+        }
+    };
+    println!(
+        "candidate {n}: logged {} in {:.2?}, replayed {} in {:.2?}",
+        verdict(logged),
+        logged.time,
+        verdict(&replayed),
+        replayed.time
+    );
+
+    let count = cmp::max(logged.commands.len(), replayed.commands.len());
+    for i in 0..count {
+        match (logged.commands.get(i), replayed.commands.get(i)) {
+            (Some(a), Some(b)) => {
+                println!("command {i}: {}", b.command);
+                if a.status != b.status {
+                    println!("  status: logged {}, replayed {}", a.status, b.status);
+                }
+                for (name, a, b) in [
+                    ("stdout", &a.stdout, &b.stdout),
+                    ("stderr", &a.stderr, &b.stderr),
+                ] {
+                    if a != b {
+                        println!("  {name} differs:");
+                        println!("    logged:   {:?}", String::from_utf8_lossy(a));
+                        println!("    replayed: {:?}", String::from_utf8_lossy(b));
+                    }
+                }
+            }
+            (Some(a), None) => println!("command {i}: only logged: {}", a.command),
+            (None, Some(b)) => println!("command {i}: only replayed: {}", b.command),
+            (None, None) => unreachable!(),
+        }
+    }
+
+    Ok(logged.interesting == replayed.interesting)
+}
+
+fn string(arg: Option<OsString>, missing: &str) -> Result<String, String> {
+    arg.ok_or(missing)?
+        .into_string()
+        .map_err(|_| "arguments must be UTF-8".to_owned())
+}
+
+fn regex(arg: Option<OsString>, missing: &str) -> Result<Regex, String> {
+    Regex::new(&string(arg, missing)?).map_err(|e| e.to_string())
+}
+
+fn main() -> Result<ExitCode, Box<dyn Error>> {
     tracing_subscriber::fmt::init();
-    let file = syn::parse_file(r#"#![allow(greetings)]
-    #![deny(goodbyes)]
-    
-    pub fn main() -> () {
-        if false {
-            std::println!("goodbye, world");
+
+    let args = parse_args().unwrap_or_else(|e| {
+        if !e.is_empty() {
+            eprintln!("error: {e}");
+        }
+        eprint!("{USAGE}");
+        process::exit(2)
+    });
+
+    let (position, source) = if args.resume {
+        checkpoint::load(&args.checkpoint)?
+    } else {
+        (Position::default(), fs::read_to_string(&args.file)?)
+    };
+
+    let file = syn::parse_file(&source)?;
+    // the checker is looked up in the temporary directory otherwise.
+    let checker = match args.checker {
+        Some(checker) if checker.parent().is_some_and(|d| !d.as_os_str().is_empty()) => {
+            Some(checker.canonicalize()?)
+        }
+        checker => checker,
+    };
+
+    let mut rustc = args.rustc;
+    if let Some(checker) = &checker {
+        rustc.program = checker.clone();
+    }
+
+    let rule = match args.mode {
+        Mode::Ice => {
+            rustc.flags = args.checker_args;
+            let signature = rustc
+                .ice(&source)?
+                .ok_or("rustc does not hit an internal compiler error on the file")?;
+            eprintln!("reducing ICE: {}", signature.message);
+            ReduceRule::Ice(IceOracle { rustc, signature })
+        }
+        Mode::Diagnostic => {
+            rustc.flags = args.checker_args;
+            let oracle = DiagnosticOracle {
+                rustc,
+                target: args.pattern,
+            };
+            let found = oracle.diagnostics(&source)?;
+            if !found.iter().any(|d| oracle.target.matches(d)) {
+                for d in &found {
+                    eprintln!(
+                        "found {}[{}]: {}",
+                        d.level,
+                        d.code.as_deref().unwrap_or(""),
+                        d.message
+                    );
+                }
+                return Err("rustc does not emit the diagnostic on the file".into());
+            }
+            ReduceRule::Diagnostic(oracle)
+        }
+        Mode::Differential(a, b) => {
+            let config = |flags: &str| {
+                let mut rustc = rustc.clone();
+                rustc.flags = args.checker_args.clone();
+                for flag in flags.split_whitespace() {
+                    match flag.strip_prefix('+') {
+                        Some(toolchain) => rustc.toolchain = Some(toolchain.to_owned()),
+                        None => rustc.flags.push(flag.into()),
+                    }
+                }
+                rustc
+            };
+            let miri = if args.miri {
+                let mut miri = Miri::setup("nightly")?;
+                miri.edition = rustc.edition.clone();
+                Some(miri)
+            } else {
+                None
+            };
+            let oracle = DifferentialOracle {
+                a: config(&a),
+                b: config(&b),
+                timeout: args.timeout,
+                miri,
+            };
+            let differs = oracle.differs(&source).map_err(|e| match e.kind() {
+                io::ErrorKind::TimedOut => format!("{e}, see --timeout"),
+                _ => e.to_string(),
+            })?;
+            if !differs {
+                return Err(
+                    "the programs behave the same, do not compile or have undefined behavior"
+                        .into(),
+                );
+            }
+            ReduceRule::Differential(oracle)
+        }
+        Mode::Clippy(lint) => {
+            if checker.is_none() {
+                rustc.program = ClippyOracle::rustc().program;
+            }
+            rustc.flags = args.checker_args;
+            let target = if lint == "crash" {
+                let signature = rustc
+                    .ice(&source)?
+                    .ok_or("clippy does not crash on the file")?;
+                eprintln!("reducing crash: {}", signature.message);
+                ClippyTarget::Crash(signature)
+            } else {
+                ClippyTarget::Lint(args.pattern)
+            };
+            let oracle = ClippyOracle { rustc, target };
+            if !oracle.reproduces(&source)? {
+                return Err(format!("clippy does not emit {lint} on the file").into());
+            }
+            ReduceRule::Clippy(oracle)
         }
-    
-        if true {
-            std::println!("Hello, world!");
+        Mode::Rustfmt(mut target) => {
+            if let RustfmtTarget::Crash(re) = &mut target {
+                *re = args.pattern.message;
+            }
+            let mut oracle = RustfmtOracle::new(target);
+            if let Some(checker) = checker {
+                oracle.program = checker;
+            }
+            oracle.toolchain = rustc.toolchain;
+            oracle.edition = rustc.edition;
+            oracle.flags = args.checker_args;
+            if !oracle.reproduces(&source)? {
+                return Err("rustfmt does not misbehave on the file".into());
+            }
+            ReduceRule::Rustfmt(oracle)
         }
+        Mode::Checker => {
+            let invocation = Invocation {
+                program: checker.unwrap(),
+                args: args.checker_args,
+                stdin: args.stdin,
+                file_name: args.file_name,
+            };
+            match args.oracle {
+                Some(predicate) => ReduceRule::Oracle(Oracle {
+                    invocation,
+                    predicate,
+                }),
+                None => ReduceRule::Program(invocation),
+            }
+        }
+    };
+
+    let mut reducer = Reducer::new(file.lower(), rule);
+    reducer.checkpoint = Some(args.checkpoint.clone());
+    reducer.retry = args.retry;
+    reducer.verify_interval = args.verify;
+    if args.keep_scratch {
+        reducer.keep_scratch = true;
+        eprintln!("scratch directory: {}", reducer.scratch_dir()?.display());
+    }
+    if let Some(n) = args.replay {
+        let dir = args.log.ok_or("--replay needs --log")?;
+        if !replay(&reducer, &dir, n)? {
+            return Ok(ExitCode::FAILURE);
+        }
+        return Ok(ExitCode::SUCCESS);
     }
-    "#)?;
+    reducer.log = args.log;
 
-    let node = file.lower();
-    let reducer = Reducer {
-        root: node,
-        rule: ReduceRule::Program(Path::new("rustc").to_owned()),
+    // on SIGINT or SIGTERM, stop the checker and write the best result so
+    // far. A second signal exits right away, which skips destructors, so
+    // the scratch directory is removed here.
+    let cancel = reducer.cancel.clone();
+    let scratch = match reducer.keep_scratch {
+        true => None,
+        false => Some(reducer.scratch_dir()?.to_owned()),
     };
+    ctrlc::set_handler(move || {
+        if cancel.swap(true, Ordering::Relaxed) {
+            if let Some(dir) = &scratch {
+                let _ = fs::remove_dir_all(dir);
+            }
+            process::exit(130);
+        }
+    })?;
 
-    reducer.reduce()?;
+    if io::stderr().is_terminal() {
+        let last = Cell::new(Instant::now());
+        reducer.progress = Some(Box::new(move |stats| {
+            if last.get().elapsed() > Duration::from_millis(100) {
+                last.set(Instant::now());
+                eprint!("\r\x1b[K{}", status(stats));
+            }
+        }));
+    }
 
-    println!("Reduced: {}", reducer.root);
+    let interrupted = match reducer.reduce_from(position) {
+        Ok(_) => false,
+        Err(e) if e.kind() == io::ErrorKind::Interrupted => true,
+        Err(e) => {
+            if reducer.progress.is_some() {
+                eprintln!();
+            }
+            eprintln!("error: {e}");
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    if reducer.progress.is_some() {
+        eprintln!();
+    }
+    summary(&reducer.stats());
+    if let Some(path) = args.report {
+        fs::write(path, serde_json::to_string_pretty(&*reducer.report())?)?;
+    }
+
+    match args.output {
+        Some(path) => fs::write(path, reducer.root.to_string())?,
+        None => println!("{}", reducer.root),
+    }
+
+    if interrupted {
+        eprintln!("interrupted, wrote the best result so far");
+        return Ok(ExitCode::from(130));
+    }
+    if !args.keep_checkpoint {
+        match fs::remove_file(&args.checkpoint) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
 
-    Ok(())
+    Ok(ExitCode::SUCCESS)
 }
@@ -0,0 +1,77 @@
+//! Saving the progress of a reduction to disk, so that it can be resumed.
+//!
+//! A checkpoint is the current best program preceded by a comment line
+//! recording where the reduction is at, so it is still a valid Rust file
+//! that can be given to the checker directly.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use tempfile::NamedTempFile;
+
+/// start of the first line of a checkpoint.
+const HEADER: &str = "// ducere checkpoint:";
+
+/// name of the stage reducing the tree with the reducer's schedule.
+pub const TREE: &str = "tree";
+
+/// name of the stage written once the reduction is finished.
+pub const DONE: &str = "done";
+
+/// where a reduction is at.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Position {
+    /// number of rounds of the tree and all passes that have completed.
+    pub round: usize,
+    /// name of the pass being run, [`TREE`] or [`DONE`]. Empty to start
+    /// from the beginning of the round.
+    pub stage: String,
+}
+
+/// Atomically write the checkpoint to `path`.
+///
+/// The file is written next to `path` and then renamed over it, so a crash
+/// while saving leaves the previous checkpoint intact.
+pub fn save(path: &Path, position: &Position, source: &str) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let mut file = NamedTempFile::new_in(dir)?;
+    writeln!(
+        file,
+        "{HEADER} round={} stage={}",
+        position.round, position.stage
+    )?;
+    writeln!(file, "{source}")?;
+    file.persist(path)?;
+    Ok(())
+}
+
+/// Read a checkpoint written by [`save`], returning the position and the
+/// source of the program.
+pub fn load(path: &Path) -> io::Result<(Position, String)> {
+    let contents = fs::read_to_string(path)?;
+    let (header, source) = contents.split_once('\n').unwrap_or((&contents, ""));
+
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} is not a checkpoint", path.display()),
+        )
+    };
+
+    let fields = header.strip_prefix(HEADER).ok_or_else(invalid)?;
+    let mut position = Position::default();
+    for field in fields.split_whitespace() {
+        match field.split_once('=') {
+            Some(("round", round)) => position.round = round.parse().map_err(|_| invalid())?,
+            Some(("stage", stage)) => position.stage = stage.to_owned(),
+            _ => return Err(invalid()),
+        }
+    }
+
+    Ok((position, source.to_owned()))
+}
@@ -40,7 +40,7 @@ impl TokenCountingVec {
     }
 
     pub fn push(&mut self, node: Node) {
-        self.tokens += node.tokens;
+        self.tokens += node.tokens();
         self.vec.push(node);
     }
 }
@@ -48,7 +48,7 @@ impl TokenCountingVec {
 impl FromIterator<Node> for TokenCountingVec {
     fn from_iter<T: IntoIterator<Item = Node>>(iter: T) -> Self {
         let mut tokens = 0;
-        let vec = iter.into_iter().inspect(|n| tokens += n.tokens).collect();
+        let vec = iter.into_iter().inspect(|n| tokens += n.tokens()).collect();
         Self { tokens, vec }
     }
 }
@@ -1,98 +1,220 @@
+use std::cmp;
+use std::ops::Range;
+
 pub trait Criteria<T> {
     fn passes<'a, I: IntoIterator<Item = &'a T>>(&mut self, iter: I) -> bool
     where
         T: 'a;
 }
 
-/// inner function of `ddmin`
-fn ddmin_inner<T, C: Criteria<T>>(items: &mut Vec<T>, test: &mut C, chunk_size: usize) {
-    // iterator that yields index of the chunk
-    fn chunks_helper<T>(
-        slice: &[T],
-        chunk_size: usize,
-    ) -> impl Iterator<Item = (usize, &[T])> + '_ {
-        let mut idx = 0;
-        slice.chunks(chunk_size).map(move |it| {
-            let idx_before = idx;
-            idx += chunk_size;
-            (idx_before, it)
-        })
+/// Use delta debugging to find the minimal set of items that passes a
+/// certain criteria.
+///
+/// The result is 1-minimal: removing any single item from it makes it
+/// fail the criteria, see [`is_one_minimal`].
+///
+/// The initial set must pass the criteria, it is not tested again.
+///
+/// # Reference
+///
+/// See https://dl.acm.org/doi/10.1145/3180155.3180236
+pub fn ddmin<T, C: Criteria<T>>(items: &mut Vec<T>, test: &mut C) {
+    // iterator that yields the range of each chunk
+    fn chunks_helper(len: usize, chunk_size: usize) -> impl Iterator<Item = Range<usize>> {
+        (0..len)
+            .step_by(chunk_size)
+            .map(move |start| start..cmp::min(start + chunk_size, len))
     }
-    // Step 1: test if individual chunks pass,
-    // in that case remove all other chunks
-    {
-        let result = chunks_helper(items, chunk_size)
-            .find(|(_, chunk)| test.passes(chunk.iter()))
-            .map(|(a, chunk)| (a, chunk.len()));
-
-        // remove all other items
-        if let Some((chunk_index, chunk_len)) = result {
-            // remove items on the right
-            if chunk_index + chunk_len < items.len() {
-                items.drain(chunk_index + chunk_len..);
+
+    let mut chunk_size = items.len() / 2;
+
+    loop {
+        // catch cases where `items` cannot be divided.
+        match &items[..] {
+            [] => return,
+            [_] if test.passes([]) => {
+                items.clear();
+                return;
             }
+            [_] => return,
+            _ => {}
+        }
+
+        // Step 1: test if individual chunks pass,
+        // in that case remove all other chunks
+        let result =
+            chunks_helper(items.len(), chunk_size).find(|range| test.passes(&items[range.clone()]));
 
-            items.drain(..chunk_index);
-            ddmin(items, test);
+        if let Some(range) = result {
+            items.truncate(range.end);
+            items.drain(..range.start);
+            chunk_size = items.len() / 2;
+            continue;
+        }
+
+        // Step 2: test if the inverse of an individual chunk will pass.
+        let result = chunks_helper(items.len(), chunk_size)
+            .find(|range| test.passes(without(items, |n| range.contains(&n))));
+
+        if let Some(range) = result {
+            items.drain(range);
+            chunk_size = items.len() / 2;
+            continue;
+        }
+
+        // Step 3: try to divide chunks more.
+        if chunk_size == 1 {
+            // step 2 has just tried to remove every single item
+            // from the current items, so they are 1-minimal.
             return;
         }
+        chunk_size /= 2;
     }
-    // Step 2: test if the inverse of an individual chunk will pass.
-    {
-        let result = chunks_helper(items, chunk_size)
-            .find(|&(index, chunk)| {
-                let range = index..index + chunk.len();
-                test.passes(
-                    items
-                        .iter()
-                        .enumerate()
-                        .filter_map(|(n, t)| (!range.contains(&n)).then(|| t)),
-                )
-            })
-            .map(|(a, chunk)| (a, chunk.len()));
-
-        if let Some((chunk_index, chunk_len)) = result {
-            items.drain(chunk_index..chunk_index + chunk_len);
-            ddmin(items, test);
-            return;
+}
+
+/// Whether removing any single item makes `items` fail the criteria.
+///
+/// This runs the test once for every item, stopping early when an item
+/// can be removed.
+pub fn is_one_minimal<T, C: Criteria<T>>(items: &[T], test: &mut C) -> bool {
+    (0..items.len()).all(|i| !test.passes(without(items, |n| n == i)))
+}
+
+/// an algorithm that finds a small subset of items passing a criteria.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Minimizer {
+    /// classic delta debugging, see [`ddmin`].
+    #[default]
+    Ddmin,
+    /// try to remove each item once, see [`linear`].
+    Linear,
+    /// remove runs of items found by binary search, see [`binary_from_end`].
+    BinaryFromEnd,
+    /// probabilistic delta debugging, see [`probdd`].
+    ProbDd,
+}
+
+impl Minimizer {
+    pub const ALL: [Minimizer; 4] = [
+        Minimizer::Ddmin,
+        Minimizer::Linear,
+        Minimizer::BinaryFromEnd,
+        Minimizer::ProbDd,
+    ];
+
+    pub fn minimize<T, C: Criteria<T>>(self, items: &mut Vec<T>, test: &mut C) {
+        match self {
+            Minimizer::Ddmin => ddmin(items, test),
+            Minimizer::Linear => linear(items, test),
+            Minimizer::BinaryFromEnd => binary_from_end(items, test),
+            Minimizer::ProbDd => probdd(items, test),
         }
     }
-    // Step 3: try to divide chunks more.
-    {
-        let new_chunk_size = chunk_size / 2;
+}
+
+/// the items that are not in `removed`.
+fn without<'a, T>(
+    items: &'a [T],
+    removed: impl Fn(usize) -> bool + 'a,
+) -> impl Iterator<Item = &'a T> + 'a {
+    items
+        .iter()
+        .enumerate()
+        .filter(move |&(i, _)| !removed(i))
+        .map(|(_, t)| t)
+}
 
-        if new_chunk_size != 0 {
-            ddmin_inner(items, test, new_chunk_size);
+/// Try to remove each item once, starting from the end.
+///
+/// This takes exactly one test per item, which is cheaper than [`ddmin`]
+/// for short lists where most items are needed.
+pub fn linear<T, C: Criteria<T>>(items: &mut Vec<T>, test: &mut C) {
+    for i in (0..items.len()).rev() {
+        if test.passes(without(items, |n| n == i)) {
+            items.remove(i);
         }
     }
 }
 
-/// Use delta debugging to find the minimal set of items that passes a
-/// certain criteria.
+/// Starting from the end, binary search for the longest run of items
+/// ending at the current position that can be removed.
 ///
-/// # Panics
+/// This works well when the items to remove are clustered, e.g. everything
+/// after the interesting part of a function body.
+pub fn binary_from_end<T, C: Criteria<T>>(items: &mut Vec<T>, test: &mut C) {
+    let mut end = items.len();
+    while end > 0 {
+        if !test.passes(without(items, |n| n + 1 == end)) {
+            end -= 1;
+            continue;
+        }
+
+        // removing `lo` items passes, removing more than `hi` does not.
+        let (mut lo, mut hi) = (1, end);
+        while lo < hi {
+            let mid = (lo + hi).div_ceil(2);
+            if test.passes(without(items, |n| (end - mid..end).contains(&n))) {
+                lo = mid;
+            } else {
+                hi = mid - 1;
+            }
+        }
+
+        items.drain(end - lo..end);
+        // removing the item before the run as well was tested to fail.
+        end = (end - lo).saturating_sub(1);
+    }
+}
+
+/// Probabilistic delta debugging.
 ///
-/// This function may panic if the initial set does not pass the criteria.
+/// Each item has a probability of being needed. Every step removes the
+/// set of least likely needed items that maximizes the expected number of
+/// items removed. If the test fails, the probabilities of those items
+/// are raised. Items known to be needed are never tested again.
 ///
 /// # Reference
 ///
-/// See https://dl.acm.org/doi/10.1145/3180155.3180236
-pub fn ddmin<T, C: Criteria<T>>(items: &mut Vec<T>, test: &mut C) {
-    // the test must pass for the input set.
-    debug_assert!(test.passes(items.iter()));
-
-    // catch cases where `items` cannot be divided.
-    match &items[..] {
-        [] => return,
-        [_] if test.passes([]) => {
-            items.clear();
+/// See https://dl.acm.org/doi/10.1145/3468264.3468625
+pub fn probdd<T, C: Criteria<T>>(items: &mut Vec<T>, test: &mut C) {
+    const INITIAL: f64 = 0.1;
+
+    let mut probs = vec![INITIAL; items.len()];
+
+    loop {
+        let mut order: Vec<_> = (0..items.len()).filter(|&i| probs[i] < 1.0).collect();
+        if order.is_empty() {
             return;
         }
-        [_] => return,
-        _ => {}
-    }
+        order.sort_by(|&a, &b| probs[a].total_cmp(&probs[b]));
 
-    let chunk_size = items.len() / 2;
+        // maximize the expected gain, `k * P(none of the k items is needed)`
+        let (mut k, mut best, mut none_needed) = (0, 0.0, 1.0);
+        let mut p = 1.0;
+        for (n, &i) in order.iter().enumerate() {
+            p *= 1.0 - probs[i];
+            let gain = (n + 1) as f64 * p;
+            if gain > best {
+                (k, best, none_needed) = (n + 1, gain, p);
+            }
+        }
+
+        let mut removed = vec![false; items.len()];
+        for &i in &order[..k] {
+            removed[i] = true;
+        }
 
-    ddmin_inner(items, test, chunk_size)
+        if test.passes(without(items, |n| removed[n])) {
+            let mut remove = removed.iter();
+            items.retain(|_| !remove.next().unwrap());
+            let mut remove = removed.iter();
+            probs.retain(|_| !remove.next().unwrap());
+        } else if k == 1 {
+            probs[order[0]] = 1.0;
+        } else {
+            for &i in &order[..k] {
+                probs[i] = (probs[i] / (1.0 - none_needed)).min(1.0);
+            }
+        }
+    }
 }
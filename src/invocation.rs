@@ -0,0 +1,73 @@
+use std::ffi::OsString;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// How to run a checker on a candidate.
+///
+/// The checker is run in the directory of the candidate, with the absolute
+/// path of the candidate in the `REDUCE_FILE` environment variable.
+#[derive(Clone, Debug)]
+pub struct Invocation {
+    pub program: PathBuf,
+    /// Arguments, where `{}` is replaced by the file name of the candidate.
+    /// Without any `{}`, the file name is passed as the last argument,
+    /// unless the candidate is passed on stdin.
+    pub args: Vec<OsString>,
+    /// pass the candidate on stdin, which is empty otherwise.
+    pub stdin: bool,
+    /// Name of the candidate file, like `test.rs` for scripts that expect
+    /// it, instead of a random `reduced*.rs`.
+    pub file_name: Option<OsString>,
+}
+
+impl Invocation {
+    /// run `program` with the file name as the only argument.
+    pub fn new(program: impl Into<PathBuf>) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+            stdin: false,
+            file_name: None,
+        }
+    }
+
+    /// the command checking the candidate `file`.
+    pub fn command(&self, file: &Path) -> io::Result<Command> {
+        let name = file.file_name().unwrap();
+        let mut command = Command::new(&self.program);
+        command
+            .current_dir(file.parent().unwrap())
+            .env("REDUCE_FILE", file);
+
+        let mut placeholder = false;
+        for arg in &self.args {
+            match arg.to_str() {
+                Some(s) if s.contains("{}") => {
+                    placeholder = true;
+                    command.arg(s.replace("{}", &name.to_string_lossy()));
+                }
+                _ => {
+                    command.arg(arg);
+                }
+            }
+        }
+        if !placeholder && !self.stdin {
+            command.arg(name);
+        }
+
+        if self.stdin {
+            command.stdin(File::open(file)?);
+        } else {
+            command.stdin(Stdio::null());
+        }
+        Ok(command)
+    }
+}
+
+impl From<PathBuf> for Invocation {
+    fn from(program: PathBuf) -> Self {
+        Self::new(program)
+    }
+}
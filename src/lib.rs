@@ -3,23 +3,51 @@
 pub(crate) mod counting;
 pub use counting::TokenCountingVec;
 
+mod invocation;
+pub use invocation::Invocation;
+
+pub mod checkpoint;
 pub mod dd;
+pub mod log;
 pub mod lower;
+pub mod oracle;
+pub mod passes;
+pub mod progress;
+pub mod report;
+mod run;
+
+#[cfg(test)]
+mod tests;
 
-use std::cell::RefCell;
+use std::cell::{Cell, OnceCell, Ref, RefCell};
+use std::cmp;
+use std::collections::BinaryHeap;
 use std::mem::{self, discriminant};
-use std::path::PathBuf;
-use std::process::Command;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
 use std::rc::Rc;
-use std::{fmt, io};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{fmt, fs, io};
 
-use dd::Criteria;
+use checkpoint::Position;
+use dd::{Criteria, Minimizer};
+use lower::Lower;
+use oracle::{
+    ClippyOracle, DiagnosticOracle, DifferentialOracle, IceOracle, IceSignature, Oracle,
+    RustfmtOracle,
+};
+use passes::Pass;
+use progress::Stats;
+use report::Report;
 
 use proc_macro2::TokenStream;
+use quote::ToTokens;
 use smol_str::SmolStr;
-use tempfile::{Builder, NamedTempFile};
+use tempfile::{Builder, NamedTempFile, TempDir};
 
-use tracing::info;
+use tracing::{info, warn};
 
 /// how a node is organized
 #[derive(Clone)]
@@ -125,7 +153,7 @@ pub struct Node {
     optional: OptionalStatus,
     rule: ReplacementRule,
     children: Rc<RefCell<Vec<Node>>>,
-    tokens: usize,
+    tokens: Cell<usize>,
 }
 
 impl Node {
@@ -135,7 +163,7 @@ impl Node {
             kind: RefCell::new(kind),
             rule,
             children: Rc::new(RefCell::new(children.vec)),
-            tokens: children.tokens,
+            tokens: Cell::new(children.tokens),
             optional: OptionalStatus::Required,
         }
     }
@@ -153,10 +181,51 @@ impl Node {
             optional: OptionalStatus::Optional,
             rule: ReplacementRule::Exempt,
             children: Rc::default(),
-            tokens: 0,
+            tokens: Cell::new(0),
         }
     }
 
+    /// number of tokens in the node and its descendants.
+    #[inline]
+    pub fn tokens(&self) -> usize {
+        self.tokens.get()
+    }
+
+    /// what the node is, e.g. the tokens of a leaf.
+    #[inline]
+    pub fn kind(&self) -> Ref<'_, NodeKind> {
+        self.kind.borrow()
+    }
+
+    /// the children of the node, in source order.
+    #[inline]
+    pub fn children(&self) -> Ref<'_, Vec<Node>> {
+        self.children.borrow()
+    }
+
+    /// Recompute the token counts of the node and its descendants after
+    /// they were changed in place, returning the new count.
+    fn recount(&self) -> usize {
+        let tokens = match &*self.kind.borrow() {
+            // leaves keep the count they were created with,
+            // deleted nodes are set to zero when deleting them.
+            NodeKind::Regular { s } if !s.is_empty() => return self.tokens(),
+            _ => self.children.borrow().iter().map(Node::recount).sum(),
+        };
+        self.tokens.set(tokens);
+        tokens
+    }
+
+    /// number of nodes in the tree, including this one.
+    fn nodes(&self) -> usize {
+        1 + self
+            .children
+            .borrow()
+            .iter()
+            .map(Node::nodes)
+            .sum::<usize>()
+    }
+
     #[inline]
     pub(crate) fn token(s: SmolStr) -> Self {
         Self {
@@ -164,52 +233,703 @@ impl Node {
             optional: OptionalStatus::Required,
             rule: ReplacementRule::Exempt,
             children: Rc::default(),
-            tokens: 1,
+            tokens: Cell::new(1),
         }
     }
 }
 
 pub enum ReduceRule {
     Fn(Box<dyn Fn(NamedTempFile) -> bool>),
-    Program(PathBuf),
+    /// decide on the source in process, without writing it to a file.
+    Source(Box<dyn Fn(&str) -> bool>),
+    /// Decide on the tree in process. While testing a candidate, replaced
+    /// parts of the tree are [`NodeKind::Temp`] nodes holding the printed
+    /// replacement, without children.
+    Tree(Box<dyn Fn(&Node) -> bool>),
+    /// run a command, the candidate is interesting if it succeeds.
+    Program(Invocation),
+    /// run a command and decide on its output, see [`oracle`].
+    Oracle(Oracle),
+    /// compile with rustc, looking for the same internal compiler error.
+    Ice(IceOracle),
+    /// compile with rustc, looking for a diagnostic.
+    Diagnostic(DiagnosticOracle),
+    /// compile and run with two configurations, looking for a difference.
+    Differential(DifferentialOracle),
+    /// run clippy, looking for a lint or crash.
+    Clippy(ClippyOracle),
+    /// run rustfmt, looking for a crash or bad formatting.
+    Rustfmt(RustfmtOracle),
+}
+
+impl ReduceRule {
+    /// the checker run by the rule, if it runs one chosen by the user.
+    fn invocation(&self) -> Option<&Invocation> {
+        match self {
+            ReduceRule::Program(invocation) => Some(invocation),
+            ReduceRule::Oracle(oracle) => Some(&oracle.invocation),
+            _ => None,
+        }
+    }
+}
+
+/// the scratch directory of a reducer.
+enum Scratch {
+    /// removed when dropped.
+    Temp(TempDir),
+    Kept(PathBuf),
+}
+
+impl Scratch {
+    fn path(&self) -> &Path {
+        match self {
+            Scratch::Temp(dir) => dir.path(),
+            Scratch::Kept(path) => path,
+        }
+    }
+}
+
+/// prefix of the names of the files candidates are written to.
+pub(crate) const TEMP_PREFIX: &str = "reduced";
+
+/// the order in which nodes of the tree are reduced.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Schedule {
+    /// reduce a node, then each of its children in source order.
+    #[default]
+    DepthFirst,
+    /// reduce all nodes at one depth before moving on to the next, like
+    /// hierarchical delta debugging.
+    Levels,
+    /// always reduce the pending node with the most tokens next, so that
+    /// stopping early still leaves the biggest wins taken.
+    LargestFirst,
+}
+
+/// How many times to run the checker on each candidate, for checkers that
+/// are not deterministic, like ones reproducing a race.
+#[derive(Clone, Copy, Debug)]
+pub struct Retry {
+    /// most runs of the checker on a candidate.
+    pub runs: usize,
+    /// Runs that must find the candidate interesting for it to be. Runs
+    /// stop as soon as the outcome is decided.
+    pub required: usize,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self {
+            runs: 1,
+            required: 1,
+        }
+    }
+}
+
+impl Retry {
+    /// Fails unless at least one run is required and no more than there
+    /// are runs, as no candidate would be checked otherwise.
+    pub fn validate(&self) -> io::Result<()> {
+        if self.required == 0 || self.required > self.runs {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "invalid retry policy {}/{}, expected 0 < <k> <= <n>",
+                    self.required, self.runs
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// How many times a rolled back program is checked before giving up on
+/// it, each with the [`Retry`] of the reducer.
+const RECHECKS: usize = 3;
+
+/// the error unwinding a reduction whose best tree is no longer
+/// interesting, to roll it back.
+#[derive(Debug)]
+struct NoLongerInteresting;
+
+impl fmt::Display for NoLongerInteresting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the reduced program is no longer interesting")
+    }
+}
+
+impl std::error::Error for NoLongerInteresting {}
+
+/// a node waiting to be reduced by [`Schedule::LargestFirst`].
+struct Pending {
+    tokens: usize,
+    /// order of insertion, so that nodes of the same size are reduced in
+    /// source order.
+    seq: usize,
+    children: Rc<RefCell<Vec<Node>>>,
+    index: usize,
+}
+
+impl Pending {
+    fn key(&self) -> (usize, cmp::Reverse<usize>) {
+        (self.tokens, cmp::Reverse(self.seq))
+    }
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Pending {}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pending {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
 }
 
 pub struct Reducer {
     pub root: Node,
     pub rule: ReduceRule,
+    /// passes run on the whole file once the tree cannot be reduced further.
+    pub passes: Vec<Box<dyn Pass>>,
+    pub schedule: Schedule,
+    /// algorithm used to minimize the children of kleene nodes.
+    pub minimizer: Minimizer,
+    /// How many levels below a node to look for a descendant to replace it
+    /// with. Descendants further down are only reached over several rounds
+    /// once the nodes in between are, which needs those to be interesting
+    /// too.
+    pub replace_depth: u8,
+    /// where to save the progress of the reduction, see [`checkpoint`].
+    pub checkpoint: Option<PathBuf>,
+    /// Minimum time between two checkpoints saved after accepted changes.
+    /// A checkpoint is always saved when a new stage starts.
+    pub checkpoint_interval: Duration,
+    /// Set to stop the reduction as soon as possible, e.g. from a signal
+    /// handler. A running checker is killed and [`Reducer::reduce`] returns
+    /// an error of kind [`io::ErrorKind::Interrupted`], leaving `root` at
+    /// the best result so far.
+    pub cancel: Arc<AtomicBool>,
+    /// called with the statistics after every run of the checker.
+    pub progress: Option<progress::Callback>,
+    /// Keep the scratch directory candidates are checked in, instead of
+    /// removing it with the reducer, to look at what the checker left.
+    pub keep_scratch: bool,
+    scratch: OnceCell<Scratch>,
+    /// where to record every candidate tested, see [`log`].
+    pub log: Option<PathBuf>,
+    log_file: RefCell<Option<log::Log>>,
+    pub retry: Retry,
+    /// How often to test the best tree so far again, to catch a flaky
+    /// checker having accepted a candidate it should not have. A tree that
+    /// is no longer interesting is rolled back to the last one that was.
+    pub verify_interval: Option<Duration>,
+    /// when the tree was last verified, and its source then.
+    verified: RefCell<Option<(Instant, String)>>,
+    /// the commands run on the candidate being tested.
+    runs: RefCell<Vec<log::Run>>,
+    stats: RefCell<Stats>,
+    report: RefCell<Report>,
+    /// index of the running strategy in `report`.
+    strategy: Cell<Option<usize>>,
+    position: RefCell<Position>,
+    last_checkpoint: Cell<Option<Instant>>,
 }
 
 impl Reducer {
+    pub fn new(root: Node, rule: ReduceRule) -> Self {
+        Self {
+            stats: RefCell::new(Stats::new(root.tokens())),
+            root,
+            rule,
+            passes: passes::default_passes(),
+            schedule: Schedule::default(),
+            minimizer: Minimizer::default(),
+            replace_depth: 4,
+            checkpoint: None,
+            checkpoint_interval: Duration::from_secs(30),
+            cancel: Arc::default(),
+            progress: None,
+            keep_scratch: false,
+            scratch: OnceCell::new(),
+            log: None,
+            log_file: RefCell::default(),
+            retry: Retry::default(),
+            verify_interval: None,
+            verified: RefCell::default(),
+            runs: RefCell::default(),
+            report: RefCell::default(),
+            strategy: Cell::new(None),
+            position: RefCell::default(),
+            last_checkpoint: Cell::new(None),
+        }
+    }
+
+    /// Record the candidates tested until the returned guard is dropped as
+    /// part of the strategy `name`.
+    fn strategy(&self, name: &str) -> StrategyGuard<'_> {
+        let index = self.report.borrow_mut().strategy(name);
+        StrategyGuard {
+            reducer: self,
+            index,
+            outer: self.strategy.replace(Some(index)),
+            tokens: self.root.tokens(),
+            start: Instant::now(),
+        }
+    }
+
+    /// statistics of the running or last reduction.
+    pub fn stats(&self) -> Ref<'_, Stats> {
+        self.stats.borrow()
+    }
+
+    /// The report of the running or last reduction, also returned by
+    /// [`Reducer::reduce`]. Useful after it was interrupted.
+    pub fn report(&self) -> Ref<'_, Report> {
+        self.report.borrow()
+    }
+
+    fn check_cancelled(&self) -> io::Result<()> {
+        if self.cancel.load(Ordering::Relaxed) {
+            Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "the reduction was cancelled",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
     /// what we do here is write the file to disk, invoke user-specified checker program,
     /// and wait.
     fn try_(&self) -> io::Result<bool> {
+        self.check_cancelled()?;
+
+        let source = self.root.to_string();
+        let Retry { runs, required } = self.retry;
+        let (mut passed, mut failed) = (0, 0);
+        let mut time = Duration::ZERO;
+        while passed < required && failed + required <= runs {
+            let entry = self.check_tree(&source, &self.root)?;
+            if entry.interesting {
+                passed += 1;
+            } else {
+                failed += 1;
+            }
+            time += entry.time;
+            self.stats.borrow_mut().checker.record(entry.time);
+
+            if let Some(dir) = &self.log {
+                let mut log = self.log_file.borrow_mut();
+                if log.is_none() {
+                    *log = Some(log::Log::open(dir)?);
+                }
+                log.as_mut().unwrap().write(&entry, &source)?;
+            }
+        }
+        let res = passed >= required;
+
+        if let Some(i) = self.strategy.get() {
+            let strategy = &mut self.report.borrow_mut().strategies[i];
+            strategy.tried += 1;
+            strategy.accepted += usize::from(res);
+        }
+
+        let mut stats = self.stats.borrow_mut();
+        stats.tokens = self.root.tokens();
+        stats.flaky += usize::from(passed > 0 && failed > 0);
+        stats.record(time, res);
+        if let Some(progress) = &self.progress {
+            progress(&stats);
+        }
+
+        Ok(res)
+    }
+
+    /// Run the checker on `source` like on a candidate, recording the
+    /// commands it runs, e.g. to replay a candidate from a [`log`].
+    pub fn check(&self, source: &str) -> io::Result<log::Entry> {
+        match &self.rule {
+            ReduceRule::Tree(_) => {
+                let file = syn::parse_file(source)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                self.check_tree(source, &file.lower())
+            }
+            _ => self.check_tree(source, &self.root),
+        }
+    }
+
+    /// like [`Reducer::check`], with `tree` being the source as a tree.
+    fn check_tree(&self, source: &str, tree: &Node) -> io::Result<log::Entry> {
+        self.runs.take();
+        let start = Instant::now();
+        let interesting = self.run_checker(source, tree)?;
+        Ok(log::Entry {
+            hash: log::hash(source),
+            interesting,
+            time: start.elapsed(),
+            commands: self.runs.take(),
+        })
+    }
+
+    fn run_checker(&self, source: &str, tree: &Node) -> io::Result<bool> {
         use io::Write;
 
-        let mut tempfile = Builder::new().prefix("reduced").suffix(".rs").tempfile()?;
-        write!(tempfile.as_file_mut(), "{}", &self.root)?;
+        match &self.rule {
+            ReduceRule::Source(f) => return Ok(f(source)),
+            ReduceRule::Tree(f) => return Ok(f(tree)),
+            _ => {}
+        }
+
+        // start from an empty directory, so that nothing a checker left
+        // behind can change the verdict on the next candidate.
+        let dir = self.scratch_dir()?;
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                fs::remove_dir_all(path)?;
+            } else {
+                fs::remove_file(path)?;
+            }
+        }
+
+        let mut builder = Builder::new();
+        match self.rule.invocation().and_then(|i| i.file_name.as_ref()) {
+            Some(name) => builder.prefix(name).rand_bytes(0),
+            None => builder.prefix(TEMP_PREFIX).suffix(".rs"),
+        };
+        let mut tempfile = builder.tempfile_in(dir)?;
+        write!(tempfile.as_file_mut(), "{source}")?;
+        if let ReduceRule::Fn(f) = &self.rule {
+            return Ok(f(tempfile));
+        }
+        // the file goes with the rest of the directory, until then it is
+        // there to look at if the directory is kept.
+        let (_, file) = tempfile.keep()?;
+        let file = file.as_path();
 
         match &self.rule {
-            ReduceRule::Fn(f) => Ok(f(tempfile)),
-            ReduceRule::Program(prog) => {
-                let status = Command::new(prog)
-                    .current_dir(tempfile.path().parent().unwrap())
-                    .arg(tempfile.path().file_name().unwrap())
-                    .status()?;
-
-                Ok(status.success())
+            ReduceRule::Fn(_) | ReduceRule::Source(_) | ReduceRule::Tree(_) => unreachable!(),
+            ReduceRule::Program(invocation) => {
+                // captured for the log, like the output of the other rules.
+                let output = self.output(&mut invocation.command(file)?, None)?;
+                Ok(output.is_some_and(|o| o.status.success()))
+            }
+            ReduceRule::Oracle(oracle) => {
+                let mut command = oracle.invocation.command(file)?;
+                if oracle.predicate.wants_backtrace() {
+                    command.env("RUST_BACKTRACE", "1");
+                }
+
+                let output = self.output(&mut command, None)?;
+                Ok(output.is_some_and(|o| oracle.predicate.matches(&o)))
+            }
+            ReduceRule::Ice(ice) => {
+                let output = self.output(&mut ice.rustc.command(file, dir), None)?;
+                Ok(output.is_some_and(|o| {
+                    let stderr = String::from_utf8_lossy(&o.stderr);
+                    IceSignature::parse(&stderr).is_some_and(|s| ice.signature.matches(&s))
+                }))
+            }
+            ReduceRule::Diagnostic(diagnostic) => {
+                let output = self.output(&mut diagnostic.command(file, dir), None)?;
+                Ok(output.is_some_and(|o| diagnostic.matches(&String::from_utf8_lossy(&o.stderr))))
             }
+            ReduceRule::Differential(differential) => {
+                differential.test(file, &mut |command, timeout| self.output(command, timeout))
+            }
+            ReduceRule::Clippy(clippy) => {
+                let output = self.output(&mut clippy.command(file, dir), None)?;
+                Ok(output.is_some_and(|o| clippy.matches(&o)))
+            }
+            ReduceRule::Rustfmt(rustfmt) => {
+                rustfmt.test(file, &mut |command, timeout| self.output(command, timeout))
+            }
+        }
+    }
+
+    /// The directory candidates are written to and checked in, created on
+    /// first use. Its contents are removed before each candidate.
+    pub fn scratch_dir(&self) -> io::Result<&Path> {
+        if self.scratch.get().is_none() {
+            let dir = Builder::new().prefix("ducere").tempdir()?;
+            let scratch = if self.keep_scratch {
+                Scratch::Kept(dir.keep())
+            } else {
+                Scratch::Temp(dir)
+            };
+            let _ = self.scratch.set(scratch);
         }
+        Ok(self.scratch.get().unwrap().path())
+    }
+
+    /// Run the command, capturing its output and recording it in `runs`.
+    /// Returns `None` if it was killed after running longer than `timeout`.
+    fn output(
+        &self,
+        command: &mut Command,
+        timeout: Option<Duration>,
+    ) -> io::Result<Option<Output>> {
+        let finished = run::run(command, timeout, &self.cancel)?;
+        let output = finished.status.map(|status| Output {
+            status,
+            stdout: finished.stdout.clone(),
+            stderr: finished.stderr.clone(),
+        });
+        self.runs.borrow_mut().push(log::Run {
+            command: format!("{command:?}"),
+            status: finished
+                .status
+                .map_or("timed out".to_owned(), |s| s.to_string()),
+            stdout: finished.stdout,
+            stderr: finished.stderr,
+        });
+        Ok(output)
     }
 
     fn try_replace_node_with(&self, node: &Node, s: String) -> io::Result<bool> {
         let prev_kind = mem::replace(&mut *node.kind.borrow_mut(), NodeKind::Temp(s));
+        // children are printed after the temp string, take them as well.
+        let prev_children = node.children.take();
         let res = self.try_();
         *node.kind.borrow_mut() = prev_kind;
+        *node.children.borrow_mut() = prev_children;
         res
     }
 
+    /// Save a checkpoint with the program returned by `source`, unless one
+    /// was saved less than `checkpoint_interval` ago and `force` is false.
+    fn save_checkpoint(&self, force: bool, source: impl FnOnce() -> String) -> io::Result<()> {
+        let path = match &self.checkpoint {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let now = Instant::now();
+        let recent = self
+            .last_checkpoint
+            .get()
+            .is_some_and(|last| now - last < self.checkpoint_interval);
+        if force || !recent {
+            checkpoint::save(path, &self.position.borrow(), &source())?;
+            self.last_checkpoint.set(Some(now));
+        }
+
+        Ok(())
+    }
+
+    /// update token counts after the tree was changed in place.
+    fn accepted(&self) -> io::Result<()> {
+        self.root.recount();
+        self.verify()?;
+        self.save_checkpoint(false, || self.root.to_string())
+    }
+
+    /// Test the tree again if it was last verified `verify_interval` ago.
+    /// If it is no longer interesting, fails with [`NoLongerInteresting`]
+    /// for [`Reducer::reduce_from`] to roll back.
+    fn verify(&self) -> io::Result<()> {
+        let interval = match self.verify_interval {
+            Some(interval) => interval,
+            None => return Ok(()),
+        };
+        let due = self
+            .verified
+            .borrow()
+            .as_ref()
+            .is_none_or(|(at, _)| at.elapsed() >= interval);
+        if !due {
+            return Ok(());
+        }
+
+        // not a candidate of the running strategy.
+        let strategy = self.strategy.take();
+        let interesting = self.try_();
+        self.strategy.set(strategy);
+        if !interesting? {
+            return Err(io::Error::other(NoLongerInteresting));
+        }
+        *self.verified.borrow_mut() = Some((Instant::now(), self.root.to_string()));
+        Ok(())
+    }
+
+    /// Check the tree again after a rollback, passing if any of
+    /// [`RECHECKS`] checks does, so a flaky checker has to reject it
+    /// consistently.
+    fn recheck(&self) -> io::Result<bool> {
+        for _ in 0..RECHECKS {
+            if self.try_()? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Record that the stage `name` is starting and save a checkpoint.
+    ///
+    /// Returns `false` if the stage is skipped because the reduction is
+    /// resuming from a later stage of the round.
+    fn enter(&self, round: usize, name: &str, resume: &mut Option<String>) -> io::Result<bool> {
+        match resume {
+            Some(stage) if stage != name => return Ok(false),
+            Some(_) => *resume = None,
+            None => {}
+        }
+
+        *self.position.borrow_mut() = Position {
+            round,
+            stage: name.to_owned(),
+        };
+        let nodes = if name == checkpoint::TREE {
+            self.root.nodes()
+        } else {
+            0
+        };
+        self.stats.borrow_mut().enter(round, name, nodes);
+        self.verify()?;
+        self.save_checkpoint(true, || self.root.to_string())?;
+        Ok(true)
+    }
+
+    /// replace the whole tree with a freshly lowered `file`.
+    fn replace_root(&self, file: syn::File) {
+        let node = file.lower();
+        self.root.tokens.set(node.tokens());
+        *self.root.kind.borrow_mut() = node.kind.into_inner();
+        *self.root.children.borrow_mut() = node.children.take();
+    }
+
+    /// run `pass` until none of its transformations are accepted anymore.
+    ///
+    /// Returns whether any transformation was accepted.
+    fn run_pass(&self, pass: &dyn Pass) -> io::Result<bool> {
+        let _strategy = self.strategy(pass.name());
+        let mut file = match syn::parse_file(&self.root.to_string()) {
+            Ok(file) => file,
+            Err(_) => return Ok(false),
+        };
+
+        let mut n = 0;
+        let mut changed = false;
+        let mut tokens = self.root.tokens();
+
+        loop {
+            let mut candidate = file.clone();
+            if !pass.transform(&mut candidate, n) {
+                break;
+            }
+
+            // e.g. inlining an item used in many places grows the program.
+            let candidate_tokens = candidate.clone().lower().tokens();
+            if candidate_tokens >= tokens {
+                n += 1;
+                continue;
+            }
+
+            let s = candidate.to_token_stream().to_string();
+            let accepted = match self.try_replace_node_with(&self.root, s.clone()) {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    // keep what was accepted before the error.
+                    if changed {
+                        self.replace_root(file);
+                    }
+                    return Err(e);
+                }
+            };
+            if accepted {
+                file = candidate;
+                changed = true;
+                tokens = candidate_tokens;
+                self.save_checkpoint(false, || s)?;
+            } else {
+                n += 1;
+            }
+        }
+
+        if changed {
+            info!("reduced via {}", pass.name());
+            self.replace_root(file);
+        }
+
+        Ok(changed)
+    }
+
+    /// reduce `node` and then its children, in source order.
     fn reduce_inner(&self, node: &Node) -> io::Result<()> {
+        self.reduce_node(node)?;
+
+        for c in &*node.children.borrow() {
+            self.reduce_inner(c)?;
+        }
+
+        Ok(())
+    }
+
+    /// reduce the tree one depth at a time, so that large subtrees anywhere
+    /// in the file are reduced before descending into the details.
+    fn reduce_levels(&self) -> io::Result<()> {
+        self.reduce_node(&self.root)?;
+
+        let mut level = vec![self.root.children.clone()];
+        while !level.is_empty() {
+            let mut next = Vec::new();
+            for children in &level {
+                for c in &*children.borrow() {
+                    self.reduce_node(c)?;
+                    next.push(c.children.clone());
+                }
+            }
+            level = next;
+        }
+
+        Ok(())
+    }
+
+    /// reduce the largest pending node first, using a max-heap.
+    fn reduce_largest_first(&self) -> io::Result<()> {
+        let mut heap = BinaryHeap::new();
+        let mut seq = 0;
+        let mut push_children = |heap: &mut BinaryHeap<Pending>, node: &Node| {
+            for (index, c) in node.children.borrow().iter().enumerate() {
+                heap.push(Pending {
+                    tokens: c.tokens(),
+                    seq,
+                    children: node.children.clone(),
+                    index,
+                });
+                seq += 1;
+            }
+        };
+
+        self.reduce_node(&self.root)?;
+        push_children(&mut heap, &self.root);
+
+        while let Some(pending) = heap.pop() {
+            let children = pending.children.borrow();
+            let node = &children[pending.index];
+            self.reduce_node(node)?;
+            push_children(&mut heap, node);
+        }
+
+        Ok(())
+    }
+
+    /// reduce the node itself, without recursing into its children.
+    fn reduce_node(&self, node: &Node) -> io::Result<()> {
+        self.stats.borrow_mut().nodes_done += 1;
+
         if let OptionalStatus::Optional = node.optional {
+            let _strategy = self.strategy("optional");
             // if we can delete the thing..
             if self.try_replace_node_with(node, String::new())? {
                 // .. replace it with whitespace. Not empty string though,
@@ -220,8 +940,9 @@ impl Reducer {
                 // .. and remove its children.
                 node.children.borrow_mut().clear();
 
-                let tokens = node.tokens;
+                let tokens = node.tokens.replace(0);
                 info!("deleted {tokens} tokens by removing optional node");
+                self.accepted()?;
 
                 // nothing to recurse
                 return Ok(());
@@ -232,73 +953,219 @@ impl Reducer {
         match &*kind {
             NodeKind::KleeneStar | NodeKind::KleenePlus => {
                 drop(kind);
+                let _strategy = self.strategy(&format!("kleene/{:?}", self.minimizer));
                 // temporarily take children from the node.
                 let mut items = mem::take(&mut *node.children.borrow_mut());
                 // the branch criteria will replace the kleene node
                 // with a temp string containing formatted node. It's children must be empty.
-                dd::ddmin(
-                    &mut items,
-                    &mut Branch {
-                        reducer: self,
-                        kleene: node,
-                    },
-                );
+                let mut branch = Branch {
+                    reducer: self,
+                    kleene: node,
+                    error: None,
+                };
+                // minimizers only drop items after a test passed, so the
+                // items are still interesting after an error.
+                self.minimizer.minimize(&mut items, &mut branch);
 
-                let token_diff = node.tokens - items.iter().map(|n| n.tokens).sum::<usize>();
+                let token_diff = node.tokens() - items.iter().map(Node::tokens).sum::<usize>();
+                *node.children.borrow_mut() = items;
 
                 if token_diff > 0 {
                     info!("deleted {token_diff} tokens via delta debugging");
+                    self.accepted()?;
+                }
+                if let Some(e) = branch.error {
+                    return Err(e);
                 }
-
-                *node.children.borrow_mut() = items;
             }
             NodeKind::Regular { .. } => {
                 drop(kind);
+                let _strategy = self.strategy(&format!("replace/{:?}", node.rule));
 
                 let replacee = &node.rule;
 
-                let mut queue = vec![(node.children.clone(), 0u8)];
-                let mut best = (node.tokens, node.children.clone());
-
-                while !queue.is_empty() {
-                    let (children, depth) = queue.pop().unwrap();
+                // the children are swapped out of `node` while testing a
+                // descendant's children in their place, so search through them
+                // separately.
+                let original = Rc::new(RefCell::new(node.children.take()));
+                let mut queue = vec![(original.clone(), 0u8)];
+                let mut best = (node.tokens(), original.clone());
+                let mut error = None;
 
+                'search: while let Some((children, depth)) = queue.pop() {
                     for c in &*children.borrow() {
-                        if c.rule.replaces(replacee) && c.tokens < best.0 {
-                            let prevchildren = node.children.replace(c.children.take());
+                        if c.rule.replaces(replacee) && c.tokens() < best.0 {
+                            *node.children.borrow_mut() = c.children.take();
                             let result = self.try_();
-                            *c.children.borrow_mut() = node.children.replace(prevchildren);
+                            *c.children.borrow_mut() = node.children.take();
 
-                            if result? {
-                                best = (c.tokens, c.children.clone());
+                            match result {
+                                Ok(true) => best = (c.tokens(), c.children.clone()),
+                                Ok(false) => {}
+                                Err(e) => {
+                                    error = Some(e);
+                                    break 'search;
+                                }
                             }
                         }
 
-                        if depth < 4 {
+                        if depth < self.replace_depth {
                             queue.push((c.children.clone(), depth + 1));
                         }
                     }
                 }
 
-                let token_diff = node.tokens - best.0;
-                if token_diff  > 0 {
+                if let Some(e) = error {
+                    *node.children.borrow_mut() = original.take();
+                    return Err(e);
+                }
+
+                let token_diff = node.tokens() - best.0;
+                *node.children.borrow_mut() = best.1.take();
+                if token_diff > 0 {
                     info!("deleted {token_diff} tokens via replacement");
-                    *node.children.borrow_mut() = best.1.take();
+                    self.accepted()?;
                 }
             }
             NodeKind::Temp(_) => unreachable!(),
         }
 
-        for c in &*node.children.borrow() {
-            self.reduce_inner(c)?;
+        Ok(())
+    }
+
+    pub fn reduce(&self) -> io::Result<Report> {
+        self.reduce_from(Position::default())
+    }
+
+    /// Reduce starting at `start`, usually loaded from a checkpoint along
+    /// with the tree.
+    ///
+    /// The stages of the round before `start.stage` are skipped. Since the
+    /// tree may have changed since they ran, the round is always followed
+    /// by a full one.
+    pub fn reduce_from(&self, start: Position) -> io::Result<Report> {
+        *self.stats.borrow_mut() = Stats::new(self.root.tokens());
+        *self.report.borrow_mut() = Report {
+            original_tokens: self.root.tokens(),
+            ..Report::default()
+        };
+        self.retry.validate()?;
+        if !self.try_()? {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the input is not interesting",
+            ));
         }
+        *self.verified.borrow_mut() = Some((Instant::now(), self.root.to_string()));
 
-        Ok(())
+        let mut start = start;
+        let result = loop {
+            match self.reduce_rounds(start) {
+                Err(e) if e.get_ref().is_some_and(|e| e.is::<NoLongerInteresting>()) => {
+                    warn!(
+                        "{e}, the checker may be flaky; rolling back to the last verified program"
+                    );
+                    let (_, source) = self.verified.borrow().clone().unwrap();
+                    let file = syn::parse_file(&source)
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                    self.replace_root(file);
+                    self.stats.borrow_mut().rollbacks += 1;
+                    // if the environment of the checker changed, rolling
+                    // back would fail the same way forever.
+                    match self.recheck() {
+                        Ok(true) => *self.verified.borrow_mut() = Some((Instant::now(), source)),
+                        Ok(false) => {
+                            break Err(io::Error::other(
+                                "the last verified program is no longer interesting either",
+                            ))
+                        }
+                        Err(e) => break Err(e),
+                    }
+                    // the round may have been partly done on the bad tree.
+                    start = Position {
+                        round: self.position.borrow().round,
+                        stage: String::new(),
+                    };
+                }
+                result => break result,
+            }
+        };
+        self.stats.borrow_mut().tokens = self.root.tokens();
+        {
+            let mut report = self.report.borrow_mut();
+            report.tokens = self.root.tokens();
+            report.time = self.stats.borrow().started.elapsed();
+        }
+        if let Err(e) = &result {
+            if e.kind() == io::ErrorKind::Interrupted {
+                // the tree is restored after every candidate, save it to
+                // resume later.
+                self.save_checkpoint(true, || self.root.to_string())?;
+            }
+        }
+        result.map(|()| self.report.borrow().clone())
     }
 
-    pub fn reduce(&self) -> io::Result<()> {
-        assert!(self.try_()?);
-        self.reduce_inner(&self.root)
+    fn reduce_rounds(&self, start: Position) -> io::Result<()> {
+        let known = [passes::UnusedItems.name(), checkpoint::TREE]
+            .into_iter()
+            .chain(self.passes.iter().map(|p| p.name()))
+            .any(|name| name == start.stage);
+        let mut resume = known.then_some(start.stage);
+        let mut round = start.round;
+
+        loop {
+            let partial = resume.is_some();
+
+            // deleting everything unused at once is a lot cheaper than
+            // delta debugging the items when it works.
+            if self.enter(round, passes::UnusedItems.name(), &mut resume)? {
+                self.run_pass(&passes::UnusedItems)?;
+            }
+            if self.enter(round, checkpoint::TREE, &mut resume)? {
+                match self.schedule {
+                    Schedule::DepthFirst => self.reduce_inner(&self.root)?,
+                    Schedule::Levels => self.reduce_levels()?,
+                    Schedule::LargestFirst => self.reduce_largest_first()?,
+                }
+            }
+
+            let mut changed = partial;
+            for pass in &self.passes {
+                if self.enter(round, pass.name(), &mut resume)? {
+                    changed |= self.run_pass(&**pass)?;
+                }
+            }
+
+            // the passes enable new reductions on the tree.
+            if !changed {
+                self.enter(round, checkpoint::DONE, &mut None)?;
+                return Ok(());
+            }
+            round += 1;
+        }
+    }
+}
+
+/// records the time and tokens removed by a strategy when dropped, see
+/// [`Reducer::strategy`].
+struct StrategyGuard<'a> {
+    reducer: &'a Reducer,
+    index: usize,
+    /// the strategy this one runs within, if any.
+    outer: Option<usize>,
+    /// tokens in the tree when the strategy started.
+    tokens: usize,
+    start: Instant,
+}
+
+impl Drop for StrategyGuard<'_> {
+    fn drop(&mut self) {
+        let reducer = self.reducer;
+        let strategy = &mut reducer.report.borrow_mut().strategies[self.index];
+        strategy.tokens_removed += self.tokens as isize - reducer.root.tokens() as isize;
+        strategy.time += self.start.elapsed();
+        reducer.strategy.set(self.outer);
     }
 }
 
@@ -308,6 +1175,8 @@ pub struct Branch<'a> {
     reducer: &'a Reducer,
     /// the kleene node that we are working on.
     kleene: &'a Node,
+    /// the first error running the checker, after which all tests fail.
+    error: Option<io::Error>,
 }
 
 impl Criteria<Node> for Branch<'_> {
@@ -315,6 +1184,10 @@ impl Criteria<Node> for Branch<'_> {
     where
         Node: 'a,
     {
+        if self.error.is_some() {
+            return false;
+        }
+
         let mut iter = iter.into_iter().peekable();
         if let NodeKind::KleenePlus = &*self.kleene.kind.borrow() {
             // kleene plus does not allow an empty sequence.
@@ -327,9 +1200,12 @@ impl Criteria<Node> for Branch<'_> {
             &mut *self.kleene.kind.borrow_mut(),
             NodeKind::Temp(iter.map(|n| format!("{n} ")).collect()),
         );
-        let res = self.reducer.try_().unwrap();
+        let res = self.reducer.try_();
         *self.kleene.kind.borrow_mut() = prev_kind;
-        res
+        res.unwrap_or_else(|e| {
+            self.error = Some(e);
+            false
+        })
     }
 }
 
@@ -0,0 +1,166 @@
+//! A record of every candidate tested during a reduction, to find out what
+//! went wrong afterwards, e.g. with a flaky checker.
+//!
+//! The log directory holds `log.jsonl` with a line for each candidate, in
+//! the order they were tested, the source of each candidate in
+//! `sources/<hash>.rs` and the output of each command the checker ran in
+//! `outputs/<candidate>.<command>.stdout` and `.stderr`, where candidates
+//! and commands are numbered from 0.
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, File, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+/// the test of one candidate.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Entry {
+    /// hash of the source, see [`hash`].
+    pub hash: String,
+    pub interesting: bool,
+    pub time: Duration,
+    /// the commands run to test the candidate, in order.
+    pub commands: Vec<Run>,
+}
+
+/// a command run to test a candidate.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Run {
+    /// the program and its arguments, quoted.
+    pub command: String,
+    /// how the command exited, like `exit status: 1`, or `timed out`.
+    pub status: String,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+/// Hash of a source for naming it in the log. Only stable for the same
+/// build of ducere.
+pub fn hash(source: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// appends entries to a log directory.
+pub(crate) struct Log {
+    dir: PathBuf,
+    index: File,
+    /// number of the next candidate.
+    next: usize,
+}
+
+impl Log {
+    /// Open the log in `dir`, creating it if needed. Entries are added
+    /// after the existing ones, e.g. when resuming a reduction.
+    pub(crate) fn open(dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir.join("sources"))?;
+        fs::create_dir_all(dir.join("outputs"))?;
+        let path = dir.join("log.jsonl");
+        let next = match File::open(&path) {
+            Ok(file) => BufReader::new(file).lines().count(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e),
+        };
+        let index = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Self {
+            dir: dir.to_owned(),
+            index,
+            next,
+        })
+    }
+
+    pub(crate) fn write(&mut self, entry: &Entry, source: &str) -> io::Result<()> {
+        let path = source_path(&self.dir, &entry.hash);
+        if !path.exists() {
+            fs::write(path, source)?;
+        }
+
+        let mut commands = Vec::new();
+        for (i, run) in entry.commands.iter().enumerate() {
+            let (stdout, stderr) = output_paths(&self.dir, self.next, i);
+            fs::write(stdout, &run.stdout)?;
+            fs::write(stderr, &run.stderr)?;
+            commands.push(json!({ "command": run.command, "status": run.status }));
+        }
+
+        let line = json!({
+            "candidate": self.next,
+            "hash": entry.hash,
+            "interesting": entry.interesting,
+            "time": entry.time.as_secs_f64(),
+            "commands": commands,
+        });
+        writeln!(self.index, "{line}")?;
+        self.next += 1;
+        Ok(())
+    }
+}
+
+/// Read the entries of the log in `dir`, the candidate numbers being
+/// indices.
+pub fn read(dir: &Path) -> io::Result<Vec<Entry>> {
+    let invalid = |line: usize| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid log entry on line {}", line + 1),
+        )
+    };
+
+    let file = File::open(dir.join("log.jsonl"))?;
+    let mut entries = Vec::new();
+    for (candidate, line) in BufReader::new(file).lines().enumerate() {
+        let line: Value = serde_json::from_str(&line?).map_err(|_| invalid(candidate))?;
+        let string = |v: &Value| {
+            v.as_str()
+                .map(str::to_owned)
+                .ok_or_else(|| invalid(candidate))
+        };
+
+        let mut commands = Vec::new();
+        let runs = line["commands"]
+            .as_array()
+            .ok_or_else(|| invalid(candidate))?;
+        for (i, run) in runs.iter().enumerate() {
+            let (stdout, stderr) = output_paths(dir, candidate, i);
+            commands.push(Run {
+                command: string(&run["command"])?,
+                status: string(&run["status"])?,
+                stdout: fs::read(stdout)?,
+                stderr: fs::read(stderr)?,
+            });
+        }
+
+        entries.push(Entry {
+            hash: string(&line["hash"])?,
+            interesting: line["interesting"]
+                .as_bool()
+                .ok_or_else(|| invalid(candidate))?,
+            time: Duration::from_secs_f64(line["time"].as_f64().ok_or_else(|| invalid(candidate))?),
+            commands,
+        });
+    }
+    Ok(entries)
+}
+
+/// the source of a candidate in the log in `dir`.
+pub fn source(dir: &Path, entry: &Entry) -> io::Result<String> {
+    fs::read_to_string(source_path(dir, &entry.hash))
+}
+
+fn source_path(dir: &Path, hash: &str) -> PathBuf {
+    dir.join("sources").join(format!("{hash}.rs"))
+}
+
+fn output_paths(dir: &Path, candidate: usize, command: usize) -> (PathBuf, PathBuf) {
+    let outputs = dir.join("outputs");
+    (
+        outputs.join(format!("{candidate}.{command}.stdout")),
+        outputs.join(format!("{candidate}.{command}.stderr")),
+    )
+}
@@ -0,0 +1,140 @@
+//! Built-in interestingness tests deciding on the output of a command,
+//! see [`ReduceRule::Oracle`](crate::ReduceRule::Oracle).
+
+mod parse;
+pub use parse::ParseError;
+
+mod rustc;
+pub use rustc::{normalize, IceOracle, IceSignature, Rustc};
+
+mod diagnostic;
+pub use diagnostic::{diagnostics, Diagnostic, DiagnosticOracle, DiagnosticPattern};
+
+mod differential;
+pub use differential::{DifferentialOracle, Miri};
+
+mod clippy;
+pub use clippy::{ClippyOracle, ClippyTarget};
+
+mod rustfmt;
+pub use rustfmt::{RustfmtOracle, RustfmtTarget};
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus, Output};
+use std::str::FromStr;
+use std::time::Duration;
+
+use regex::Regex;
+use tempfile::{Builder, TempDir};
+
+use crate::{Invocation, TEMP_PREFIX};
+
+/// Runs a command on the candidate and tests its output with `predicate`.
+#[derive(Clone, Debug)]
+pub struct Oracle {
+    pub invocation: Invocation,
+    pub predicate: Predicate,
+}
+
+/// something about the output of a command.
+#[derive(Clone, Debug)]
+pub enum Predicate {
+    /// stdout matches the regex.
+    Stdout(Regex),
+    /// stderr matches the regex.
+    Stderr(Regex),
+    /// stdout or stderr matches the regex.
+    Output(Regex),
+    /// the command exited with the code.
+    Exit(i32),
+    /// The command was killed by the signal, or by any signal if `None`.
+    /// Never true on platforms without signals.
+    Signal(Option<i32>),
+    /// The command hit an internal compiler error, with stderr matching
+    /// the regex if there is one. Commands are run with `RUST_BACKTRACE=1`
+    /// so that the regex can match the backtrace.
+    Ice(Option<Regex>),
+    And(Vec<Predicate>),
+    Or(Vec<Predicate>),
+    Not(Box<Predicate>),
+}
+
+impl Predicate {
+    pub fn matches(&self, output: &Output) -> bool {
+        let stdout = || String::from_utf8_lossy(&output.stdout);
+        let stderr = || String::from_utf8_lossy(&output.stderr);
+
+        match self {
+            Predicate::Stdout(re) => re.is_match(&stdout()),
+            Predicate::Stderr(re) => re.is_match(&stderr()),
+            Predicate::Output(re) => re.is_match(&stdout()) || re.is_match(&stderr()),
+            Predicate::Exit(code) => output.status.code() == Some(*code),
+            Predicate::Signal(signal) => match (signal, self::signal(output.status)) {
+                (_, None) => false,
+                (None, Some(_)) => true,
+                (Some(expected), Some(actual)) => *expected == actual,
+            },
+            Predicate::Ice(re) => {
+                let stderr = stderr();
+                IceSignature::parse(&stderr).is_some()
+                    && re.as_ref().is_none_or(|re| re.is_match(&stderr))
+            }
+            Predicate::And(ps) => ps.iter().all(|p| p.matches(output)),
+            Predicate::Or(ps) => ps.iter().any(|p| p.matches(output)),
+            Predicate::Not(p) => !p.matches(output),
+        }
+    }
+
+    /// whether any part of the predicate looks for an ICE.
+    pub(crate) fn wants_backtrace(&self) -> bool {
+        match self {
+            Predicate::Ice(_) => true,
+            Predicate::And(ps) | Predicate::Or(ps) => ps.iter().any(Predicate::wants_backtrace),
+            Predicate::Not(p) => p.wants_backtrace(),
+            _ => false,
+        }
+    }
+}
+
+/// Parses predicates like `ice(/broken MIR/) || (stderr(/E0308/) && !exit(0))`.
+///
+/// Atoms are `stdout(/re/)`, `stderr(/re/)`, `output(/re/)`, `exit(n)`,
+/// `signal`, `signal(n)`, `ice` and `ice(/re/)`, where a `/` in a regex is
+/// written `\/`. They are combined with `!`, `&&`, `||` and parentheses.
+impl FromStr for Predicate {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse::parse(s)
+    }
+}
+
+/// runs a command with an optional time limit, returning `None` if it ran
+/// out of time.
+pub(crate) type Run<'a> =
+    dyn FnMut(&mut Command, Option<Duration>) -> io::Result<Option<Output>> + 'a;
+
+/// Write `source` to a `reduced*.rs` file in a new temporary directory,
+/// which removes the file when dropped.
+fn source_file(source: &str) -> io::Result<(TempDir, PathBuf)> {
+    let dir = tempfile::tempdir()?;
+    let mut file = Builder::new()
+        .prefix(TEMP_PREFIX)
+        .suffix(".rs")
+        .tempfile_in(dir.path())?;
+    file.write_all(source.as_bytes())?;
+    let path = file.into_temp_path().keep()?;
+    Ok((dir, path))
+}
+
+#[cfg(unix)]
+fn signal(status: ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+fn signal(_: ExitStatus) -> Option<i32> {
+    None
+}
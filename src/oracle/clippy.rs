@@ -0,0 +1,65 @@
+use std::io;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use super::{diagnostics, DiagnosticPattern, IceSignature, Rustc};
+
+/// Considers a candidate interesting if clippy still emits a lint, like a
+/// false positive, or crashes like on the original program.
+#[derive(Clone, Debug)]
+pub struct ClippyOracle {
+    /// how to run clippy, see [`ClippyOracle::rustc`].
+    pub rustc: Rustc,
+    pub target: ClippyTarget,
+}
+
+/// what clippy must do on a candidate.
+#[derive(Clone, Debug)]
+pub enum ClippyTarget {
+    /// Emit a lint matching the pattern, whose code is the lint name like
+    /// `clippy::needless_range_loop`. Lints that are allowed by default
+    /// need a flag like `-Wclippy::pedantic`.
+    Lint(DiagnosticPattern),
+    /// crash with the signature, usually from [`Rustc::ice`] on the original.
+    Crash(IceSignature),
+}
+
+impl ClippyOracle {
+    /// `clippy-driver`, which takes the same arguments as rustc.
+    pub fn rustc() -> Rustc {
+        Rustc {
+            program: "clippy-driver".into(),
+            ..Rustc::default()
+        }
+    }
+
+    /// like [`Rustc::command`], asking for diagnostics as JSON to find lints.
+    pub fn command(&self, file: &Path, out_dir: &Path) -> Command {
+        let mut command = self.rustc.command(file, out_dir);
+        if let ClippyTarget::Lint(_) = self.target {
+            command.arg("--error-format=json");
+        }
+        command
+    }
+
+    pub fn matches(&self, output: &Output) -> bool {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        match &self.target {
+            ClippyTarget::Lint(pattern) => diagnostics(&stderr).iter().any(|d| pattern.matches(d)),
+            ClippyTarget::Crash(signature) => {
+                IceSignature::parse(&stderr).is_some_and(|s| signature.matches(&s))
+            }
+        }
+    }
+
+    /// Whether clippy still lints or crashes on `source`, compiling it with
+    /// [`Rustc::output`].
+    pub fn reproduces(&self, source: &str) -> io::Result<bool> {
+        let output = self.rustc.output(source, |c| {
+            if let ClippyTarget::Lint(_) = self.target {
+                c.arg("--error-format=json");
+            }
+        })?;
+        Ok(self.matches(&output))
+    }
+}
@@ -0,0 +1,123 @@
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+use regex::Regex;
+use serde_json::Value;
+
+use super::Rustc;
+
+/// Considers a candidate interesting if rustc still emits a diagnostic
+/// matching `target`, so that the reduction can't wander off into a
+/// different error.
+#[derive(Clone, Debug)]
+pub struct DiagnosticOracle {
+    pub rustc: Rustc,
+    pub target: DiagnosticPattern,
+}
+
+impl DiagnosticOracle {
+    /// like [`Rustc::command`], asking for diagnostics as JSON.
+    pub fn command(&self, file: &Path, out_dir: &Path) -> Command {
+        let mut command = self.rustc.command(file, out_dir);
+        command.arg("--error-format=json");
+        command
+    }
+
+    /// Compile `source` and return its diagnostics, e.g. to check that the
+    /// original program has the target.
+    pub fn diagnostics(&self, source: &str) -> io::Result<Vec<Diagnostic>> {
+        let output = self.rustc.output(source, |c| {
+            c.arg("--error-format=json");
+        })?;
+        Ok(diagnostics(&String::from_utf8_lossy(&output.stderr)))
+    }
+
+    /// whether the JSON diagnostics in `stderr` contain the target.
+    pub fn matches(&self, stderr: &str) -> bool {
+        diagnostics(stderr).iter().any(|d| self.target.matches(d))
+    }
+}
+
+/// what a diagnostic must look like, `None` matches anything.
+#[derive(Clone, Debug, Default)]
+pub struct DiagnosticPattern {
+    /// the error code, like `E0308`.
+    pub code: Option<String>,
+    /// the level, like `error` or `warning`.
+    pub level: Option<String>,
+    /// matched against the primary message.
+    pub message: Option<Regex>,
+    /// matched against the source code of any of the primary spans.
+    pub span_text: Option<Regex>,
+}
+
+impl DiagnosticPattern {
+    pub fn matches(&self, d: &Diagnostic) -> bool {
+        self.code
+            .as_ref()
+            .is_none_or(|c| d.code.as_ref() == Some(c))
+            && self.level.as_ref().is_none_or(|l| d.level == *l)
+            && self
+                .message
+                .as_ref()
+                .is_none_or(|re| re.is_match(&d.message))
+            && self
+                .span_text
+                .as_ref()
+                .is_none_or(|re| d.span_text.iter().any(|t| re.is_match(t)))
+    }
+}
+
+/// a top-level diagnostic emitted by rustc.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub level: String,
+    pub code: Option<String>,
+    pub message: String,
+    /// the highlighted source code of each primary span.
+    pub span_text: Vec<String>,
+}
+
+/// Parse the diagnostics rustc prints with `--error-format=json`, one per
+/// line. Lines that are not diagnostics are skipped.
+pub fn diagnostics(stderr: &str) -> Vec<Diagnostic> {
+    stderr
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter_map(|d| {
+            Some(Diagnostic {
+                level: d["level"].as_str()?.to_owned(),
+                code: d["code"]["code"].as_str().map(str::to_owned),
+                message: d["message"].as_str()?.to_owned(),
+                span_text: d["spans"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter(|s| s["is_primary"].as_bool() == Some(true))
+                    .map(highlighted)
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+/// the highlighted part of each line of the span, joined by newlines.
+fn highlighted(span: &Value) -> String {
+    let lines = span["text"].as_array().into_iter().flatten();
+    let parts: Vec<String> = lines
+        .filter_map(|line| {
+            // columns are 1-based and count chars.
+            let start = line["highlight_start"].as_u64()? as usize;
+            let end = line["highlight_end"].as_u64()? as usize;
+            let text = line["text"].as_str()?;
+            Some(
+                text.chars()
+                    .skip(start.saturating_sub(1))
+                    .take(end.saturating_sub(start))
+                    .collect(),
+            )
+        })
+        .collect();
+    parts.join("\n")
+}
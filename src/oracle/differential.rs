@@ -0,0 +1,143 @@
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+use super::{source_file, Run, Rustc};
+use crate::run::run;
+
+/// Considers a candidate interesting if it compiles and terminates under
+/// both configurations and the runs differ in stdout or exit code, like a
+/// miscompilation at some opt-level or toolchain.
+#[derive(Clone, Debug)]
+pub struct DifferentialOracle {
+    pub a: Rustc,
+    pub b: Rustc,
+    /// longest the compiled programs may run, longer runs are not interesting.
+    pub timeout: Duration,
+    /// Also run the candidate under Miri and require it to find no
+    /// undefined behavior, which would make any difference meaningless.
+    pub miri: Option<Miri>,
+}
+
+/// how to run a program under Miri.
+#[derive(Clone, Debug)]
+pub struct Miri {
+    pub toolchain: String,
+    /// sysroot built by `cargo miri setup`.
+    pub sysroot: PathBuf,
+    pub edition: String,
+    /// other flags, like `-Zmiri-strict-provenance`.
+    pub flags: Vec<OsString>,
+}
+
+impl DifferentialOracle {
+    /// Whether the two builds of `source` behave differently when run.
+    /// Fails with [`io::ErrorKind::TimedOut`] if a program runs longer than
+    /// `timeout`.
+    pub fn differs(&self, source: &str) -> io::Result<bool> {
+        let (_dir, file) = source_file(source)?;
+        let never = AtomicBool::new(false);
+        let mut timed_out = false;
+        let differs = self.test(&file, &mut |command, timeout| {
+            let finished = run(command, timeout, &never)?;
+            timed_out |= finished.status.is_none();
+            Ok(finished.status.map(|status| Output {
+                status,
+                stdout: finished.stdout,
+                stderr: finished.stderr,
+            }))
+        })?;
+        if timed_out {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("the program runs longer than {:?}", self.timeout),
+            ));
+        }
+        Ok(differs)
+    }
+
+    /// Test the candidate `file`, compiling into its directory.
+    pub(crate) fn test(&self, file: &Path, run: &mut Run<'_>) -> io::Result<bool> {
+        let dir = file.parent().unwrap();
+        let stem = file.file_stem().unwrap();
+
+        let mut runs = Vec::with_capacity(2);
+        for (i, rustc) in [&self.a, &self.b].into_iter().enumerate() {
+            let out_dir = dir.join(i.to_string());
+            fs::create_dir_all(&out_dir)?;
+
+            let compiled = run(&mut rustc.command(file, &out_dir), None)?;
+            if !compiled.is_some_and(|o| o.status.success()) {
+                return Ok(false);
+            }
+
+            let mut program = Command::new(out_dir.join(stem));
+            program.current_dir(dir).stdin(Stdio::null());
+            match run(&mut program, Some(self.timeout))? {
+                Some(output) => runs.push((output.status.code(), output.stdout)),
+                None => return Ok(false),
+            }
+        }
+
+        if runs[0] == runs[1] {
+            return Ok(false);
+        }
+
+        // only run Miri on differences, it is slow.
+        match &self.miri {
+            Some(miri) => match run(&mut miri.command(file), Some(self.timeout))? {
+                Some(output) => Ok(Miri::accepts(&output)),
+                None => Ok(false),
+            },
+            None => Ok(true),
+        }
+    }
+}
+
+impl Miri {
+    /// Build the sysroot for `toolchain`, which needs the `miri` and
+    /// `rust-src` components.
+    pub fn setup(toolchain: &str) -> io::Result<Self> {
+        let output = Command::new("cargo")
+            .arg(format!("+{toolchain}"))
+            .args(["miri", "setup", "--print-sysroot"])
+            .stdin(Stdio::null())
+            .stderr(Stdio::inherit())
+            .output()?;
+        if !output.status.success() {
+            return Err(io::Error::other("`cargo miri setup` failed"));
+        }
+
+        Ok(Self {
+            toolchain: toolchain.to_owned(),
+            sysroot: String::from_utf8_lossy(&output.stdout).trim().into(),
+            edition: "2021".to_owned(),
+            flags: Vec::new(),
+        })
+    }
+
+    pub fn command(&self, file: &Path) -> Command {
+        let mut command = Command::new("rustup");
+        command
+            .args(["run", &self.toolchain, "miri", "--sysroot"])
+            .arg(&self.sysroot)
+            .arg("--edition")
+            .arg(&self.edition)
+            .args(&self.flags)
+            .arg(file)
+            .stdin(Stdio::null());
+        command
+    }
+
+    /// Whether Miri found neither undefined behavior nor an operation it
+    /// can't check. The program itself may fail.
+    pub fn accepts(output: &Output) -> bool {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        !stderr.contains("error: Undefined Behavior")
+            && !stderr.contains("error: unsupported operation")
+    }
+}
@@ -0,0 +1,191 @@
+use std::error::Error;
+use std::fmt;
+
+use regex::Regex;
+
+use super::Predicate;
+
+/// an error in the syntax of a [`Predicate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// byte offset into the input.
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
+    }
+}
+
+impl Error for ParseError {}
+
+pub(super) fn parse(s: &str) -> Result<Predicate, ParseError> {
+    let mut parser = Parser { s, pos: 0 };
+    let predicate = parser.or()?;
+    parser.skip_whitespace();
+    if parser.pos < s.len() {
+        return Err(parser.error("expected `&&`, `||` or the end"));
+    }
+    Ok(predicate)
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        ParseError {
+            offset: self.pos,
+            message: message.into(),
+        }
+    }
+
+    fn rest(&self) -> &str {
+        &self.s[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// consume `token` if the input continues with it.
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        let found = self.rest().starts_with(token);
+        if found {
+            self.pos += token.len();
+        }
+        found
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), ParseError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{token}`")))
+        }
+    }
+
+    fn or(&mut self) -> Result<Predicate, ParseError> {
+        let mut ps = vec![self.and()?];
+        while self.eat("||") {
+            ps.push(self.and()?);
+        }
+        Ok(if ps.len() == 1 {
+            ps.pop().unwrap()
+        } else {
+            Predicate::Or(ps)
+        })
+    }
+
+    fn and(&mut self) -> Result<Predicate, ParseError> {
+        let mut ps = vec![self.unary()?];
+        while self.eat("&&") {
+            ps.push(self.unary()?);
+        }
+        Ok(if ps.len() == 1 {
+            ps.pop().unwrap()
+        } else {
+            Predicate::And(ps)
+        })
+    }
+
+    fn unary(&mut self) -> Result<Predicate, ParseError> {
+        if self.eat("!") {
+            return Ok(Predicate::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("(") {
+            let p = self.or()?;
+            self.expect(")")?;
+            return Ok(p);
+        }
+
+        self.skip_whitespace();
+        let start = self.pos;
+        let name_len = self
+            .rest()
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(self.rest().len());
+        self.pos += name_len;
+        let name = &self.s[start..self.pos];
+
+        let p = match name {
+            "stdout" => Predicate::Stdout(self.regex_arg()?),
+            "stderr" => Predicate::Stderr(self.regex_arg()?),
+            "output" => Predicate::Output(self.regex_arg()?),
+            "exit" => Predicate::Exit(self.int_arg()?),
+            "signal" => Predicate::Signal(self.optional(Self::int_arg)?),
+            "ice" => Predicate::Ice(self.optional(Self::regex_arg)?),
+            _ => {
+                self.pos = start;
+                return Err(self.error("expected a predicate"));
+            }
+        };
+        Ok(p)
+    }
+
+    /// an argument that may be left out along with the parentheses.
+    fn optional<T>(
+        &mut self,
+        arg: fn(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<Option<T>, ParseError> {
+        if self.rest().trim_start().starts_with('(') {
+            arg(self).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn int_arg(&mut self) -> Result<i32, ParseError> {
+        self.expect("(")?;
+        self.skip_whitespace();
+        let len = self
+            .rest()
+            .find(|c: char| !(c.is_ascii_digit() || c == '-'))
+            .unwrap_or(self.rest().len());
+        let n = self.rest()[..len]
+            .parse()
+            .map_err(|_| self.error("expected an integer"))?;
+        self.pos += len;
+        self.expect(")")?;
+        Ok(n)
+    }
+
+    /// `(/re/)`, where `\/` stands for a `/` in the regex.
+    fn regex_arg(&mut self) -> Result<Regex, ParseError> {
+        self.expect("(")?;
+        self.expect("/")?;
+
+        let start = self.pos;
+        let mut pattern = String::new();
+        let mut chars = self.rest().char_indices();
+        let end = loop {
+            match chars.next() {
+                Some((i, '/')) => break i,
+                Some((_, '\\')) => match chars.next() {
+                    Some((_, '/')) => pattern.push('/'),
+                    Some((_, c)) => {
+                        pattern.push('\\');
+                        pattern.push(c);
+                    }
+                    None => pattern.push('\\'),
+                },
+                Some((_, c)) => pattern.push(c),
+                None => return Err(self.error("unterminated regex")),
+            }
+        };
+        self.pos += end + 1;
+
+        let re = Regex::new(&pattern).map_err(|e| ParseError {
+            offset: start,
+            message: e.to_string(),
+        })?;
+        self.expect(")")?;
+        Ok(re)
+    }
+}
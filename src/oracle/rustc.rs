@@ -0,0 +1,189 @@
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use super::source_file;
+use crate::TEMP_PREFIX;
+
+/// how to invoke rustc.
+#[derive(Clone, Debug)]
+pub struct Rustc {
+    /// the compiler to run, `rustc` unless it is a driver like clippy's.
+    pub program: PathBuf,
+    /// Toolchain to compile with, like `nightly-2024-01-01`, selected by
+    /// the rustup proxy. `None` uses the default one.
+    pub toolchain: Option<String>,
+    pub edition: String,
+    pub crate_type: Option<String>,
+    /// other flags, like `-Zmir-opt-level=3`.
+    pub flags: Vec<OsString>,
+}
+
+impl Default for Rustc {
+    fn default() -> Self {
+        Self {
+            program: "rustc".into(),
+            toolchain: None,
+            edition: "2021".to_owned(),
+            crate_type: None,
+            flags: Vec::new(),
+        }
+    }
+}
+
+impl Rustc {
+    /// A command compiling `file`, writing outputs to `out_dir`.
+    ///
+    /// Backtraces are enabled so that ICEs print their query stack.
+    pub fn command(&self, file: &Path, out_dir: &Path) -> Command {
+        let mut command = Command::new(&self.program);
+        if let Some(toolchain) = &self.toolchain {
+            command.arg(format!("+{toolchain}"));
+        }
+        command.arg("--edition").arg(&self.edition);
+        if let Some(crate_type) = &self.crate_type {
+            command.arg("--crate-type").arg(crate_type);
+        }
+        command
+            .args(&self.flags)
+            .arg("--out-dir")
+            .arg(out_dir)
+            .arg(file)
+            .env("RUST_BACKTRACE", "1")
+            .stdin(Stdio::null());
+        command
+    }
+
+    /// Compile `source`, changing the command with `f` first.
+    ///
+    /// The source is written to a file named like the candidates of a
+    /// reduction, so that the output can be compared with theirs.
+    pub fn output(&self, source: &str, f: impl FnOnce(&mut Command)) -> io::Result<Output> {
+        let (dir, file) = source_file(source)?;
+        let mut command = self.command(&file, dir.path());
+        f(&mut command);
+        command.output()
+    }
+
+    /// Compile `source` and return the ICE it causes, if any.
+    pub fn ice(&self, source: &str) -> io::Result<Option<IceSignature>> {
+        let Output { stderr, .. } = self.output(source, |_| {})?;
+        Ok(IceSignature::parse(&String::from_utf8_lossy(&stderr)))
+    }
+}
+
+/// Considers a candidate interesting if rustc hits the same ICE as on the
+/// original program.
+#[derive(Clone, Debug)]
+pub struct IceOracle {
+    pub rustc: Rustc,
+    /// the ICE to reproduce, usually from [`Rustc::ice`] on the original.
+    /// Candidates are compiled with [`Rustc::command`].
+    pub signature: IceSignature,
+}
+
+/// what identifies an internal compiler error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IceSignature {
+    /// the panic or `bug!` message, normalized with [`normalize`].
+    pub message: String,
+    /// Names of the queries on the query stack, innermost first. Empty if
+    /// rustc did not print one, in which case it is not compared.
+    pub queries: Vec<String>,
+}
+
+impl IceSignature {
+    /// Find the ICE in the stderr of rustc.
+    ///
+    /// Understands both `error: internal compiler error: <message>` from
+    /// `bug!` and panics, with the message on the same line as the location
+    /// (older rustc) or on the following lines.
+    pub fn parse(stderr: &str) -> Option<Self> {
+        static BUG: OnceLock<Regex> = OnceLock::new();
+        static PANIC: OnceLock<Regex> = OnceLock::new();
+
+        let bug = BUG.get_or_init(|| Regex::new(r"^error: internal compiler error(.*)$").unwrap());
+        let panic = PANIC.get_or_init(|| {
+            Regex::new(r"^thread 'rustc'( \([0-9]+\))? panicked at (.*)$").unwrap()
+        });
+
+        let mut lines = stderr.lines();
+        let mut message = None;
+        while let Some(line) = lines.next() {
+            if let Some(c) = bug.captures(line) {
+                message = Some(c[1].to_owned());
+                break;
+            }
+            if let Some(c) = panic.captures(line) {
+                message = Some(match c[2].strip_suffix(':') {
+                    // the message follows on its own lines, until a `note:`.
+                    Some(location) => {
+                        let mut message = location.to_owned();
+                        for l in lines.by_ref().take_while(|l| {
+                            !l.starts_with("note:") && !l.starts_with("stack backtrace")
+                        }) {
+                            message.push('\n');
+                            message.push_str(l);
+                        }
+                        message
+                    }
+                    None => c[2].to_owned(),
+                });
+                break;
+            }
+        }
+        let message = normalize(message?.trim());
+
+        let queries = stderr
+            .lines()
+            .skip_while(|l| !l.starts_with("query stack during panic"))
+            .take_while(|l| !l.starts_with("end of query stack"))
+            .filter_map(|l| {
+                let start = l.find('[')?;
+                let end = start + l[start..].find(']')?;
+                Some(l[start + 1..end].to_owned())
+            })
+            .collect();
+
+        Some(Self { message, queries })
+    }
+
+    /// Whether `other` is the same ICE. A signature without queries
+    /// matches any query stack.
+    pub fn matches(&self, other: &IceSignature) -> bool {
+        self.message == other.message && (self.queries.is_empty() || self.queries == other.queries)
+    }
+}
+
+/// Remove what changes as the program is reduced from an ICE message: the
+/// path of the candidate file, whose name is also the crate name, and
+/// numbers, as in `DefId(0:12)` or line numbers.
+pub fn normalize(message: &str) -> String {
+    static FILE: OnceLock<Regex> = OnceLock::new();
+    static NUMBER: OnceLock<Regex> = OnceLock::new();
+
+    let file = FILE.get_or_init(|| {
+        Regex::new(&format!(
+            r"([[:alnum:]_./-]*/)?{TEMP_PREFIX}[[:alnum:]_]*(\[[[:xdigit:]]+\])?"
+        ))
+        .unwrap()
+    });
+    // error codes like E0308 stay.
+    let number = NUMBER.get_or_init(|| Regex::new(r"E[0-9]{4}|[0-9]+").unwrap());
+
+    let message = file.replace_all(message, "FILE");
+    number
+        .replace_all(&message, |c: &regex::Captures| {
+            let m = &c[0];
+            if m.starts_with('E') {
+                m.to_owned()
+            } else {
+                "N".to_owned()
+            }
+        })
+        .into_owned()
+}
@@ -0,0 +1,111 @@
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+use regex::Regex;
+
+use super::{signal, source_file, Run};
+
+/// Considers a candidate interesting if rustfmt crashes on it, formats it
+/// differently when run again, or formats it in a particular way.
+#[derive(Clone, Debug)]
+pub struct RustfmtOracle {
+    /// path or name of the formatter, looked up in `PATH` if it has no
+    /// directory.
+    pub program: PathBuf,
+    /// toolchain whose rustfmt to run, through the rustup proxy.
+    pub toolchain: Option<String>,
+    pub edition: String,
+    /// other flags, like `--config max_width=60`.
+    pub flags: Vec<OsString>,
+    pub target: RustfmtTarget,
+}
+
+/// what rustfmt must do on a candidate.
+#[derive(Clone, Debug)]
+pub enum RustfmtTarget {
+    /// Panic or get killed by a signal, like on a stack overflow, with
+    /// stderr matching the regex if there is one.
+    Crash(Option<Regex>),
+    /// format the program, then change it again when formatting that.
+    Unstable,
+    /// format the program into something matching the regex.
+    Output(Regex),
+}
+
+impl RustfmtOracle {
+    pub fn new(target: RustfmtTarget) -> Self {
+        Self {
+            program: "rustfmt".into(),
+            toolchain: None,
+            edition: "2021".to_owned(),
+            flags: Vec::new(),
+            target,
+        }
+    }
+
+    /// A command formatting `file` in place.
+    pub fn command(&self, file: &Path) -> Command {
+        let mut command = Command::new(&self.program);
+        if let Some(toolchain) = &self.toolchain {
+            command.arg(format!("+{toolchain}"));
+        }
+        command
+            .arg("--edition")
+            .arg(&self.edition)
+            .args(&self.flags)
+            .arg(file)
+            .env("RUST_BACKTRACE", "1")
+            .stdin(Stdio::null());
+        command
+    }
+
+    /// Whether rustfmt crashes or misformats `source` like it should, so
+    /// that a reduction starting from it can succeed.
+    pub fn reproduces(&self, source: &str) -> io::Result<bool> {
+        let (_dir, file) = source_file(source)?;
+        self.test(&file, &mut |command, _| command.output().map(Some))
+    }
+
+    /// Test the candidate `file`, formatting a copy next to it.
+    pub(crate) fn test(&self, file: &Path, run: &mut Run<'_>) -> io::Result<bool> {
+        // rustfmt formats in place, leave the candidate alone.
+        let dir = file.parent().unwrap().join("rustfmt");
+        fs::create_dir_all(&dir)?;
+        let copy = dir.join(file.file_name().unwrap());
+        fs::copy(file, &copy)?;
+
+        let output = match run(&mut self.command(&copy), None)? {
+            Some(output) => output,
+            None => return Ok(false),
+        };
+        if let RustfmtTarget::Crash(re) = &self.target {
+            return Ok(crashed(&output)
+                && re
+                    .as_ref()
+                    .is_none_or(|re| re.is_match(&String::from_utf8_lossy(&output.stderr))));
+        }
+        if !output.status.success() {
+            return Ok(false);
+        }
+
+        let formatted = fs::read_to_string(&copy)?;
+        match &self.target {
+            RustfmtTarget::Crash(_) => unreachable!(),
+            RustfmtTarget::Output(re) => Ok(re.is_match(&formatted)),
+            RustfmtTarget::Unstable => match run(&mut self.command(&copy), None)? {
+                Some(output) if output.status.success() => {
+                    Ok(fs::read_to_string(&copy)? != formatted)
+                }
+                _ => Ok(false),
+            },
+        }
+    }
+}
+
+/// whether rustfmt panicked or was killed, rather than rejecting the input.
+fn crashed(output: &Output) -> bool {
+    output.status.code() == Some(101) || signal(output.status).is_some()
+}
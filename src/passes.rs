@@ -0,0 +1,147 @@
+//! Passes that work on the syntax tree of the whole file.
+//!
+//! Reductions on [`Node`](crate::Node)s can only delete or replace one
+//! place at a time, so they cannot remove e.g. a generic parameter without
+//! breaking every use of it. A [`Pass`] edits the definition together with
+//! all of its uses, which is then tested as a single candidate.
+
+mod generics;
+pub use generics::GenericParams;
+
+mod params;
+pub use params::FnParams;
+
+mod inline;
+pub use inline::{InlineFns, InlineItems};
+
+mod fields;
+pub use fields::{StructFields, UnitStructs, Variants};
+
+mod traits;
+pub use traits::{DefaultBodies, TraitItems};
+
+mod sweep;
+pub use sweep::UnusedItems;
+
+use syn::punctuated::Punctuated;
+use syn::visit_mut::{self, VisitMut};
+use syn::{Block, File, Item, ItemMod, Path, Stmt};
+
+pub trait Pass {
+    /// name of the pass, used for logging.
+    fn name(&self) -> &'static str;
+
+    /// Apply the `n`th transformation this pass can find in `file`.
+    ///
+    /// Returns `false` if there are no more than `n` opportunities, in
+    /// which case `file` is left untouched. When a transformed file is
+    /// accepted the same `n` is tried again on the new file, so passes must
+    /// enumerate opportunities in a deterministic order.
+    fn transform(&self, file: &mut File, n: usize) -> bool;
+}
+
+/// the passes used by a [`Reducer`](crate::Reducer) by default.
+pub fn default_passes() -> Vec<Box<dyn Pass>> {
+    vec![
+        Box::new(GenericParams),
+        Box::new(FnParams),
+        Box::new(InlineFns),
+        Box::new(InlineItems),
+        Box::new(StructFields),
+        Box::new(Variants),
+        Box::new(UnitStructs),
+        Box::new(TraitItems),
+        Box::new(DefaultBodies),
+    ]
+}
+
+/// remove the `n`th value from a punctuated sequence.
+pub(crate) fn remove_nth<T, P: Default>(punctuated: &mut Punctuated<T, P>, n: usize) -> T {
+    let mut removed = None;
+    *punctuated = std::mem::take(punctuated)
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, v)| {
+            if i == n {
+                removed = Some(v);
+                None
+            } else {
+                Some(v)
+            }
+        })
+        .collect();
+    removed.expect("index out of bounds")
+}
+
+/// whether the last segment of the path is `name`.
+pub(crate) fn names(path: &Path, name: &str) -> bool {
+    path.segments.last().is_some_and(|s| s.ident == name)
+}
+
+/// Remove the `n`th item, in visiting order, for which `pred` returns true.
+///
+/// Items are searched for in the file, in modules and in blocks.
+pub(crate) fn take_item(
+    file: &mut File,
+    n: usize,
+    pred: impl FnMut(&Item) -> bool,
+) -> Option<Item> {
+    let mut take = TakeItem {
+        pred,
+        n,
+        taken: None,
+    };
+    take.visit_file_mut(file);
+    take.taken
+}
+
+struct TakeItem<F> {
+    pred: F,
+    n: usize,
+    taken: Option<Item>,
+}
+
+impl<F: FnMut(&Item) -> bool> TakeItem<F> {
+    /// whether `item` is the one we are looking for.
+    fn hit(&mut self, item: &Item) -> bool {
+        if self.taken.is_some() || !(self.pred)(item) {
+            return false;
+        }
+        if self.n == 0 {
+            return true;
+        }
+        self.n -= 1;
+        false
+    }
+}
+
+impl<F: FnMut(&Item) -> bool> VisitMut for TakeItem<F> {
+    fn visit_file_mut(&mut self, file: &mut File) {
+        if let Some(i) = file.items.iter().position(|item| self.hit(item)) {
+            self.taken = Some(file.items.remove(i));
+        }
+        visit_mut::visit_file_mut(self, file);
+    }
+
+    fn visit_item_mod_mut(&mut self, module: &mut ItemMod) {
+        if let Some((_, items)) = &mut module.content {
+            if let Some(i) = items.iter().position(|item| self.hit(item)) {
+                self.taken = Some(items.remove(i));
+            }
+        }
+        visit_mut::visit_item_mod_mut(self, module);
+    }
+
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        let found = block
+            .stmts
+            .iter()
+            .position(|stmt| matches!(stmt, Stmt::Item(item) if self.hit(item)));
+        if let Some(i) = found {
+            if let Stmt::Item(item) = block.stmts.remove(i) {
+                self.taken = Some(item);
+            }
+        }
+        visit_mut::visit_block_mut(self, block);
+    }
+}
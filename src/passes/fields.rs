@@ -0,0 +1,330 @@
+use syn::visit::{self, Visit};
+use syn::visit_mut::{self, VisitMut};
+use syn::*;
+
+use super::{remove_nth, Pass};
+
+/// Removes a field from a struct or enum variant, along with the field in
+/// every struct expression, struct pattern or tuple constructor naming it.
+pub struct StructFields;
+
+impl Pass for StructFields {
+    fn name(&self) -> &'static str {
+        "struct-fields"
+    }
+
+    fn transform(&self, file: &mut File, n: usize) -> bool {
+        let target = Collect::ctors(file)
+            .into_iter()
+            .enumerate()
+            .flat_map(|(ordinal, (ctor, fields))| {
+                fields
+                    .into_iter()
+                    .enumerate()
+                    .map(move |(index, name)| (ordinal, ctor.clone(), Edit::Field { index, name }))
+            })
+            .nth(n);
+
+        apply(file, target)
+    }
+}
+
+/// Turns a tuple struct or tuple variant into a unit one, replacing
+/// constructor calls with the bare path.
+pub struct UnitStructs;
+
+impl Pass for UnitStructs {
+    fn name(&self) -> &'static str {
+        "unit-structs"
+    }
+
+    fn transform(&self, file: &mut File, n: usize) -> bool {
+        let target = Collect::ctors(file)
+            .into_iter()
+            .enumerate()
+            // tuple fields have no names
+            .filter(|(_, (_, fields))| !fields.is_empty() && fields.iter().all(Option::is_none))
+            .map(|(ordinal, (ctor, _))| (ordinal, ctor, Edit::Unit))
+            .nth(n);
+
+        apply(file, target)
+    }
+}
+
+/// Removes an enum variant along with the match arms (or alternatives of
+/// or-patterns) that match on it.
+pub struct Variants;
+
+impl Pass for Variants {
+    fn name(&self) -> &'static str {
+        "variants"
+    }
+
+    fn transform(&self, file: &mut File, n: usize) -> bool {
+        let target = Collect::ctors(file)
+            .into_iter()
+            .enumerate()
+            .filter(|(_, (ctor, _))| ctor.variant.is_some())
+            .map(|(ordinal, (ctor, _))| (ordinal, ctor, Edit::Variant))
+            .nth(n);
+
+        apply(file, target)
+    }
+}
+
+/// something that can be constructed by a path: a struct or a variant.
+#[derive(Clone)]
+struct Ctor {
+    ty: Ident,
+    variant: Option<Ident>,
+}
+
+impl Ctor {
+    /// Whether `path` could name this constructor. `Self` names the struct
+    /// only in its own impls, tracked by `in_impl`.
+    fn matches(&self, path: &Path, in_impl: bool) -> bool {
+        let mut segments = path.segments.iter().rev();
+        match (&self.variant, segments.next(), segments.next()) {
+            (None, Some(last), None) if last.ident == "Self" => in_impl,
+            (None, Some(last), _) => last.ident == self.ty,
+            (Some(variant), Some(last), Some(ty)) => {
+                last.ident == *variant && (ty.ident == self.ty || ty.ident == "Self")
+            }
+            _ => false,
+        }
+    }
+}
+
+enum Edit {
+    /// remove a field, `name` is `None` for tuple fields.
+    Field {
+        index: usize,
+        name: Option<Ident>,
+    },
+    Unit,
+    Variant,
+}
+
+/// collects constructors in visiting order along with their field names.
+#[derive(Default)]
+struct Collect(Vec<(Ctor, Vec<Option<Ident>>)>);
+
+impl Collect {
+    fn ctors(file: &File) -> Vec<(Ctor, Vec<Option<Ident>>)> {
+        let mut collect = Self::default();
+        collect.visit_file(file);
+        collect.0
+    }
+
+    fn push(&mut self, ctor: Ctor, fields: &Fields) {
+        let names = fields.iter().map(|f| f.ident.clone()).collect();
+        self.0.push((ctor, names));
+    }
+}
+
+impl<'ast> Visit<'ast> for Collect {
+    fn visit_item_struct(&mut self, i: &'ast ItemStruct) {
+        let ctor = Ctor {
+            ty: i.ident.clone(),
+            variant: None,
+        };
+        self.push(ctor, &i.fields);
+        visit::visit_item_struct(self, i);
+    }
+
+    fn visit_item_enum(&mut self, i: &'ast ItemEnum) {
+        for v in &i.variants {
+            let ctor = Ctor {
+                ty: i.ident.clone(),
+                variant: Some(v.ident.clone()),
+            };
+            self.push(ctor, &v.fields);
+        }
+        visit::visit_item_enum(self, i);
+    }
+}
+
+fn apply(file: &mut File, target: Option<(usize, Ctor, Edit)>) -> bool {
+    match target {
+        Some((ordinal, ctor, edit)) => {
+            Apply {
+                ordinal,
+                ctor,
+                edit,
+                current: 0,
+                in_impl: false,
+            }
+            .visit_file_mut(file);
+            true
+        }
+        None => false,
+    }
+}
+
+struct Apply {
+    /// index of the definition, in the order of [`Collect`]
+    ordinal: usize,
+    ctor: Ctor,
+    edit: Edit,
+    current: usize,
+    /// whether `Self` is the type of the constructor.
+    in_impl: bool,
+}
+
+impl Apply {
+    fn def(&mut self, fields: &mut Fields) {
+        if self.current == self.ordinal {
+            match (&self.edit, &mut *fields) {
+                (Edit::Field { index, .. }, Fields::Named(f)) => {
+                    remove_nth(&mut f.named, *index);
+                }
+                (Edit::Field { index, .. }, Fields::Unnamed(f)) => {
+                    remove_nth(&mut f.unnamed, *index);
+                }
+                (Edit::Unit, _) => *fields = Fields::Unit,
+                _ => {}
+            }
+        }
+        self.current += 1;
+    }
+
+    /// whether `path` could name the constructor here.
+    fn names(&self, path: &Path) -> bool {
+        self.ctor.matches(path, self.in_impl)
+    }
+
+    /// whether `member` is the field being removed.
+    fn removes(&self, member: &Member) -> bool {
+        match (&self.edit, member) {
+            (
+                Edit::Field {
+                    name: Some(name), ..
+                },
+                Member::Named(m),
+            ) => m == name,
+            (Edit::Field { index, name: None }, Member::Unnamed(m)) => m.index as usize == *index,
+            _ => false,
+        }
+    }
+
+    /// whether the pattern matches on the constructor anywhere.
+    fn refers(&self, pat: &Pat) -> bool {
+        struct Refers<'a>(&'a Apply, bool);
+
+        impl<'ast> Visit<'ast> for Refers<'_> {
+            fn visit_path(&mut self, path: &'ast Path) {
+                self.1 |= self.0.names(path);
+            }
+        }
+
+        let mut refers = Refers(self, false);
+        refers.visit_pat(pat);
+        refers.1
+    }
+}
+
+impl VisitMut for Apply {
+    fn visit_item_impl_mut(&mut self, i: &mut ItemImpl) {
+        let own = matches!(&*i.self_ty, Type::Path(TypePath { qself: None, path })
+            if path.segments.last().is_some_and(|s| s.ident == self.ctor.ty));
+        let outer = std::mem::replace(&mut self.in_impl, own);
+        visit_mut::visit_item_impl_mut(self, i);
+        self.in_impl = outer;
+    }
+
+    fn visit_item_struct_mut(&mut self, i: &mut ItemStruct) {
+        if self.current == self.ordinal && matches!(self.edit, Edit::Unit) {
+            i.semi_token = Some(Default::default());
+        }
+        self.def(&mut i.fields);
+        visit_mut::visit_item_struct_mut(self, i);
+    }
+
+    fn visit_item_enum_mut(&mut self, i: &mut ItemEnum) {
+        let first = self.current;
+        for v in &mut i.variants {
+            self.def(&mut v.fields);
+        }
+
+        if matches!(self.edit, Edit::Variant) && (first..self.current).contains(&self.ordinal) {
+            remove_nth(&mut i.variants, self.ordinal - first);
+        }
+        visit_mut::visit_item_enum_mut(self, i);
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        visit_mut::visit_expr_mut(self, expr);
+
+        match (&self.edit, &mut *expr) {
+            (Edit::Field { index, .. }, Expr::Call(call)) => {
+                if let Expr::Path(ExprPath { path, .. }) = &*call.func {
+                    if self.names(path) && *index < call.args.len() {
+                        remove_nth(&mut call.args, *index);
+                    }
+                }
+            }
+            (Edit::Field { .. }, Expr::Struct(s)) if self.names(&s.path) => {
+                let fields = std::mem::take(&mut s.fields);
+                s.fields = fields
+                    .into_iter()
+                    .filter(|f| !self.removes(&f.member))
+                    .collect();
+            }
+            (Edit::Unit, Expr::Call(call)) => {
+                if let Expr::Path(p) = &*call.func {
+                    if self.names(&p.path) {
+                        *expr = Expr::Path(p.clone());
+                    }
+                }
+            }
+            (Edit::Variant, Expr::Match(m)) => {
+                let arms = std::mem::take(&mut m.arms);
+                m.arms = arms
+                    .into_iter()
+                    .filter_map(|mut arm| {
+                        if let Pat::Or(or) = &mut arm.pat {
+                            let cases = std::mem::take(&mut or.cases);
+                            or.cases = cases.into_iter().filter(|p| !self.refers(p)).collect();
+                            if or.cases.is_empty() {
+                                return None;
+                            }
+                        } else if self.refers(&arm.pat) {
+                            return None;
+                        }
+                        Some(arm)
+                    })
+                    .collect();
+            }
+            _ => {}
+        }
+    }
+
+    fn visit_pat_mut(&mut self, pat: &mut Pat) {
+        visit_mut::visit_pat_mut(self, pat);
+
+        match (&self.edit, &mut *pat) {
+            (Edit::Field { index, name: None }, Pat::TupleStruct(p)) if self.names(&p.path) => {
+                // positions after `..` are counted from the end.
+                let rest = p.pat.elems.iter().position(|p| matches!(p, Pat::Rest(_)));
+                if *index < rest.unwrap_or(p.pat.elems.len()) {
+                    remove_nth(&mut p.pat.elems, *index);
+                }
+            }
+            (Edit::Field { .. }, Pat::Struct(p)) if self.names(&p.path) => {
+                let fields = std::mem::take(&mut p.fields);
+                p.fields = fields
+                    .into_iter()
+                    .filter(|f| !self.removes(&f.member))
+                    .collect();
+            }
+            (Edit::Unit, Pat::TupleStruct(p)) if self.names(&p.path) => {
+                *pat = Pat::Path(PatPath {
+                    attrs: std::mem::take(&mut p.attrs),
+                    qself: None,
+                    path: p.path.clone(),
+                });
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use syn::visit::{self, Visit};
+use syn::visit_mut::{self, VisitMut};
+use syn::*;
+
+use super::{remove_nth, Pass};
+
+/// Removes a generic parameter from a definition, along with the argument
+/// in the same position wherever a path names the definition.
+pub struct GenericParams;
+
+impl Pass for GenericParams {
+    fn name(&self) -> &'static str {
+        "generic-params"
+    }
+
+    fn transform(&self, file: &mut File, n: usize) -> bool {
+        let mut collect = Collect::default();
+        collect.visit_file(file);
+
+        let (def, name, param) = match collect.targets.into_iter().nth(n) {
+            Some(target) => target,
+            None => return false,
+        };
+
+        Apply {
+            def,
+            name,
+            param,
+            current: 0,
+        }
+        .visit_file_mut(file);
+
+        true
+    }
+}
+
+/// which argument of a path corresponds to a parameter.
+///
+/// Lifetimes can be elided at use sites independently of the other
+/// arguments, so they are counted separately.
+#[derive(Clone, Copy)]
+enum Position {
+    Lifetime(usize),
+    /// types and consts
+    Other(usize),
+}
+
+#[derive(Clone, Copy)]
+struct Param {
+    index: usize,
+    position: Position,
+}
+
+/// collects every (definition, name, parameter) that could be removed.
+#[derive(Default)]
+struct Collect {
+    defs: usize,
+    targets: Vec<(usize, String, Param)>,
+}
+
+impl Collect {
+    fn def(&mut self, ident: &Ident, generics: &Generics) {
+        let (mut lifetimes, mut others) = (0, 0);
+        for (index, param) in generics.params.iter().enumerate() {
+            let position = match param {
+                GenericParam::Lifetime(_) => {
+                    lifetimes += 1;
+                    Position::Lifetime(lifetimes - 1)
+                }
+                _ => {
+                    others += 1;
+                    Position::Other(others - 1)
+                }
+            };
+            self.targets
+                .push((self.defs, ident.to_string(), Param { index, position }));
+        }
+        self.defs += 1;
+    }
+}
+
+/// removes the parameter from the `def`th definition and all uses of `name`.
+struct Apply {
+    def: usize,
+    name: String,
+    param: Param,
+    current: usize,
+}
+
+impl Apply {
+    fn def(&mut self, generics: &mut Generics) {
+        if self.current == self.def {
+            remove_param(generics, self.param.index);
+        }
+        self.current += 1;
+    }
+}
+
+/// remove the `index`th parameter along with the where predicates on it.
+fn remove_param(generics: &mut Generics, index: usize) {
+    let removed = remove_nth(&mut generics.params, index);
+
+    if let Some(clause) = &mut generics.where_clause {
+        let predicates = std::mem::take(&mut clause.predicates);
+        clause.predicates = predicates
+            .into_iter()
+            .filter(|p| !bounds(p, &removed))
+            .collect();
+        if clause.predicates.is_empty() {
+            generics.where_clause = None;
+        }
+    }
+
+    if generics.params.is_empty() {
+        generics.lt_token = None;
+        generics.gt_token = None;
+    }
+}
+
+/// the identifiers and lifetimes in the self type and trait of an impl,
+/// which are the ones constraining its parameters.
+#[derive(Default)]
+struct Header {
+    idents: Vec<String>,
+}
+
+impl Header {
+    fn of(item: &ItemImpl) -> Self {
+        let mut header = Self::default();
+        header.visit_type(&item.self_ty);
+        if let Some((_, path, _)) = &item.trait_ {
+            header.visit_path(path);
+        }
+        header
+    }
+
+    fn uses(&self, param: &GenericParam) -> bool {
+        let name = match param {
+            GenericParam::Type(p) => p.ident.to_string(),
+            GenericParam::Lifetime(p) => p.lifetime.to_string(),
+            GenericParam::Const(p) => p.ident.to_string(),
+        };
+        self.idents.contains(&name)
+    }
+}
+
+impl<'ast> Visit<'ast> for Header {
+    fn visit_ident(&mut self, ident: &'ast Ident) {
+        self.idents.push(ident.to_string());
+    }
+
+    fn visit_lifetime(&mut self, lifetime: &'ast Lifetime) {
+        self.idents.push(lifetime.to_string());
+    }
+}
+
+/// whether the where predicate puts bounds on the parameter itself.
+fn bounds(predicate: &WherePredicate, param: &GenericParam) -> bool {
+    match (predicate, param) {
+        (WherePredicate::Type(p), GenericParam::Type(TypeParam { ident, .. })) => {
+            matches!(&p.bounded_ty, Type::Path(TypePath { qself: None, path }) if path.is_ident(ident))
+        }
+        (WherePredicate::Lifetime(p), GenericParam::Lifetime(l)) => p.lifetime == l.lifetime,
+        _ => false,
+    }
+}
+
+macro_rules! def_visitors {
+    ($($visit:ident, $visit_mut:ident($Ty:ty) => |$i:ident| ($ident:expr, $generics:expr);)*) => {
+        impl<'ast> Visit<'ast> for Collect {
+            $(
+                fn $visit(&mut self, $i: &'ast $Ty) {
+                    self.def(&$ident, &$generics);
+                    visit::$visit(self, $i);
+                }
+            )*
+        }
+
+        impl VisitMut for Apply {
+            $(
+                fn $visit_mut(&mut self, $i: &mut $Ty) {
+                    self.def(&mut $generics);
+                    visit_mut::$visit_mut(self, $i);
+                }
+            )*
+
+            fn visit_path_segment_mut(&mut self, seg: &mut PathSegment) {
+                if seg.ident == self.name {
+                    if let PathArguments::AngleBracketed(args) = &mut seg.arguments {
+                        let (lifetime, n) = match self.param.position {
+                            Position::Lifetime(n) => (true, n),
+                            Position::Other(n) => (false, n),
+                        };
+                        let found = args
+                            .args
+                            .iter()
+                            .enumerate()
+                            .filter(|(_, arg)| match arg {
+                                GenericArgument::Lifetime(_) => lifetime,
+                                GenericArgument::Type(_) | GenericArgument::Const(_) => !lifetime,
+                                _ => false,
+                            })
+                            .map(|(i, _)| i)
+                            .nth(n);
+
+                        if let Some(i) = found {
+                            remove_nth(&mut args.args, i);
+                            if args.args.is_empty() {
+                                seg.arguments = PathArguments::None;
+                            }
+                        }
+                    }
+                }
+                visit_mut::visit_path_segment_mut(self, seg);
+            }
+
+            fn visit_expr_method_call_mut(&mut self, call: &mut ExprMethodCall) {
+                if call.method == self.name {
+                    if let (Position::Other(n), Some(turbofish)) =
+                        (self.param.position, &mut call.turbofish)
+                    {
+                        if n < turbofish.args.len() {
+                            remove_nth(&mut turbofish.args, n);
+                            if turbofish.args.is_empty() {
+                                call.turbofish = None;
+                            }
+                        }
+                    }
+                }
+                visit_mut::visit_expr_method_call_mut(self, call);
+            }
+
+            fn visit_item_impl_mut(&mut self, item: &mut ItemImpl) {
+                let before = Header::of(item);
+                visit_mut::visit_item_impl_mut(self, item);
+
+                // a parameter of the impl may have been constrained by the
+                // removed argument only (E0207).
+                let after = Header::of(item);
+                for index in (0..item.generics.params.len()).rev() {
+                    let param = &item.generics.params[index];
+                    if before.uses(param) && !after.uses(param) {
+                        remove_param(&mut item.generics, index);
+                    }
+                }
+            }
+        }
+    };
+}
+
+def_visitors! {
+    visit_item_struct, visit_item_struct_mut(ItemStruct) => |i| (i.ident, i.generics);
+    visit_item_enum, visit_item_enum_mut(ItemEnum) => |i| (i.ident, i.generics);
+    visit_item_union, visit_item_union_mut(ItemUnion) => |i| (i.ident, i.generics);
+    visit_item_trait, visit_item_trait_mut(ItemTrait) => |i| (i.ident, i.generics);
+    visit_item_trait_alias, visit_item_trait_alias_mut(ItemTraitAlias) => |i| (i.ident, i.generics);
+    visit_item_type, visit_item_type_mut(ItemType) => |i| (i.ident, i.generics);
+    visit_signature, visit_signature_mut(Signature) => |i| (i.ident, i.generics);
+}
@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+
+use syn::visit::{self, Visit};
+use syn::visit_mut::{self, VisitMut};
+use syn::*;
+
+use super::{names, take_item, Pass};
+
+/// Inlines a function that is called exactly once, binding the arguments
+/// with `let`, and deletes it.
+pub struct InlineFns;
+
+impl Pass for InlineFns {
+    fn name(&self) -> &'static str {
+        "inline-fns"
+    }
+
+    fn transform(&self, file: &mut File, n: usize) -> bool {
+        let mut uses = Uses::default();
+        uses.visit_file(file);
+
+        let f = match take_item(file, n, |item| match item {
+            Item::Fn(f) => inlinable(f, &uses),
+            _ => false,
+        }) {
+            Some(Item::Fn(f)) => f,
+            _ => return false,
+        };
+
+        InlineCall { f: Some(f) }.visit_file_mut(file);
+
+        true
+    }
+}
+
+/// Inlines `type` aliases and `const` items at all of their uses and
+/// deletes them.
+pub struct InlineItems;
+
+impl Pass for InlineItems {
+    fn name(&self) -> &'static str {
+        "inline-items"
+    }
+
+    fn transform(&self, file: &mut File, n: usize) -> bool {
+        let item = take_item(file, n, |item| match item {
+            Item::Type(ty) => ty.generics.params.is_empty(),
+            Item::Const(_) => true,
+            _ => false,
+        });
+
+        match item {
+            Some(Item::Type(alias)) => InlineAlias {
+                name: alias.ident,
+                ty: paren_type(*alias.ty),
+            }
+            .visit_file_mut(file),
+            Some(Item::Const(item)) => InlineConst {
+                name: item.ident,
+                expr: paren_expr(*item.expr),
+            }
+            .visit_file_mut(file),
+            _ => return false,
+        }
+
+        true
+    }
+}
+
+/// how each identifier is used.
+#[derive(Default)]
+struct Uses {
+    /// number of paths naming the identifier.
+    paths: HashMap<String, usize>,
+    /// number of arguments of each call of a path naming the identifier.
+    calls: HashMap<String, Vec<usize>>,
+}
+
+impl<'ast> Visit<'ast> for Uses {
+    fn visit_path(&mut self, path: &'ast Path) {
+        if let Some(seg) = path.segments.last() {
+            *self.paths.entry(seg.ident.to_string()).or_default() += 1;
+        }
+        visit::visit_path(self, path);
+    }
+
+    fn visit_expr_call(&mut self, call: &'ast ExprCall) {
+        if let Expr::Path(ExprPath { path, .. }) = &*call.func {
+            if let Some(seg) = path.segments.last() {
+                let calls = self.calls.entry(seg.ident.to_string()).or_default();
+                calls.push(call.args.len());
+            }
+        }
+        visit::visit_expr_call(self, call);
+    }
+}
+
+/// Whether the function only has a single use, which is a call with all
+/// of its arguments, and its body means the same thing after being pasted
+/// at the call.
+fn inlinable(f: &ItemFn, uses: &Uses) -> bool {
+    #[derive(Default)]
+    struct Body {
+        calls_self: bool,
+        escapes: bool,
+        name: String,
+    }
+
+    impl<'ast> Visit<'ast> for Body {
+        fn visit_path(&mut self, path: &'ast Path) {
+            self.calls_self |= names(path, &self.name);
+            visit::visit_path(self, path);
+        }
+
+        fn visit_expr_return(&mut self, e: &'ast ExprReturn) {
+            self.escapes = true;
+            visit::visit_expr_return(self, e);
+        }
+
+        fn visit_expr_try(&mut self, e: &'ast ExprTry) {
+            self.escapes = true;
+            visit::visit_expr_try(self, e);
+        }
+    }
+
+    let sig = &f.sig;
+    let name = sig.ident.to_string();
+    if sig.asyncness.is_some()
+        || sig.unsafety.is_some()
+        || sig.variadic.is_some()
+        || !sig.generics.params.is_empty()
+        || uses.paths.get(&name) != Some(&1)
+        || uses.calls.get(&name) != Some(&vec![sig.inputs.len()])
+    {
+        return false;
+    }
+
+    let mut body = Body {
+        name,
+        ..Body::default()
+    };
+    body.visit_block(&f.block);
+
+    !body.calls_self && !body.escapes
+}
+
+/// replaces the call to `f` with its body.
+struct InlineCall {
+    f: Option<ItemFn>,
+}
+
+impl VisitMut for InlineCall {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        visit_mut::visit_expr_mut(self, expr);
+
+        let (call, f) = match (&mut *expr, &self.f) {
+            (Expr::Call(call), Some(f)) => (call, f),
+            _ => return,
+        };
+        match &*call.func {
+            Expr::Path(ExprPath { path, .. })
+                if names(path, &f.sig.ident.to_string())
+                    && call.args.len() == f.sig.inputs.len() => {}
+            _ => return,
+        }
+
+        let f = self.f.take().unwrap();
+        let (pats, tys): (Vec<_>, Vec<_>) = f
+            .sig
+            .inputs
+            .into_iter()
+            .filter_map(|input| match input {
+                FnArg::Typed(PatType { pat, ty, .. }) => Some((pat, ty)),
+                FnArg::Receiver(_) => None,
+            })
+            .unzip();
+        let args: Vec<Expr> = call.args.iter().cloned().collect();
+
+        let mut stmts = Vec::with_capacity(1 + f.block.stmts.len());
+        // a single `let` for all arguments, which may name the parameters.
+        match pats.len() {
+            0 => {}
+            1 => stmts.push(parse_quote!(let #(#pats)*: #(#tys)* = #(#args)*;)),
+            _ => stmts.push(parse_quote!(let (#(#pats),*): (#(#tys),*) = (#(#args),*);)),
+        }
+        stmts.extend(f.block.stmts);
+
+        *expr = Expr::Block(ExprBlock {
+            attrs: Vec::new(),
+            label: None,
+            block: Block {
+                brace_token: f.block.brace_token,
+                stmts,
+            },
+        });
+    }
+}
+
+/// replaces the alias `name` with `ty`.
+struct InlineAlias {
+    name: Ident,
+    ty: Type,
+}
+
+impl VisitMut for InlineAlias {
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        if let Type::Path(TypePath { qself: None, path }) = ty {
+            if path.is_ident(&self.name) {
+                *ty = self.ty.clone();
+                return;
+            }
+        }
+        visit_mut::visit_type_mut(self, ty);
+    }
+
+    fn visit_expr_path_mut(&mut self, e: &mut ExprPath) {
+        // `Alias::new()` becomes `<Type>::new()`
+        if e.qself.is_none() && e.path.segments.len() > 1 && e.path.segments[0].ident == self.name {
+            e.path.segments = e.path.segments.iter().skip(1).cloned().collect();
+            e.path.leading_colon = Some(Default::default());
+            e.qself = Some(QSelf {
+                lt_token: Default::default(),
+                ty: Box::new(self.ty.clone()),
+                position: 0,
+                as_token: None,
+                gt_token: Default::default(),
+            });
+            return;
+        }
+        visit_mut::visit_expr_path_mut(self, e);
+    }
+}
+
+/// replaces the constant `name` with `expr`.
+struct InlineConst {
+    name: Ident,
+    expr: Expr,
+}
+
+impl VisitMut for InlineConst {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        if let Expr::Path(ExprPath {
+            qself: None, path, ..
+        }) = expr
+        {
+            if path.is_ident(&self.name) {
+                *expr = self.expr.clone();
+                return;
+            }
+        }
+        visit_mut::visit_expr_mut(self, expr);
+    }
+}
+
+/// parenthesize types that would bind differently where they are pasted,
+/// like `&dyn A + B`.
+fn paren_type(ty: Type) -> Type {
+    let multiple_bounds = match &ty {
+        Type::TraitObject(t) => t.bounds.len() > 1,
+        Type::ImplTrait(t) => t.bounds.len() > 1,
+        _ => false,
+    };
+    if multiple_bounds {
+        Type::Paren(TypeParen {
+            paren_token: Default::default(),
+            elem: Box::new(ty),
+        })
+    } else {
+        ty
+    }
+}
+
+fn paren_expr(expr: Expr) -> Expr {
+    match expr {
+        Expr::Lit(_) | Expr::Path(_) | Expr::Paren(_) => expr,
+        expr => Expr::Paren(ExprParen {
+            attrs: Vec::new(),
+            paren_token: Default::default(),
+            expr: Box::new(expr),
+        }),
+    }
+}
@@ -0,0 +1,99 @@
+use syn::visit::{self, Visit};
+use syn::visit_mut::{self, VisitMut};
+use syn::*;
+
+use super::{names, remove_nth, Pass};
+
+/// Removes a parameter from a function or method, along with the argument
+/// in the same position at every call naming it.
+pub struct FnParams;
+
+impl Pass for FnParams {
+    fn name(&self) -> &'static str {
+        "fn-params"
+    }
+
+    fn transform(&self, file: &mut File, n: usize) -> bool {
+        let mut collect = Collect::default();
+        collect.visit_file(file);
+
+        let target = match collect.targets.into_iter().nth(n) {
+            Some(target) => target,
+            None => return false,
+        };
+
+        Apply { target, current: 0 }.visit_file_mut(file);
+
+        true
+    }
+}
+
+struct Target {
+    /// index of the signature in visiting order
+    sig: usize,
+    name: String,
+    /// index of the parameter, counting the receiver
+    index: usize,
+    receiver: bool,
+}
+
+#[derive(Default)]
+struct Collect {
+    sigs: usize,
+    targets: Vec<Target>,
+}
+
+impl<'ast> Visit<'ast> for Collect {
+    fn visit_signature(&mut self, sig: &'ast Signature) {
+        let receiver = matches!(sig.inputs.first(), Some(FnArg::Receiver(_)));
+        for (index, arg) in sig.inputs.iter().enumerate() {
+            // removing `self` would turn all method calls into errors.
+            if let FnArg::Typed(_) = arg {
+                self.targets.push(Target {
+                    sig: self.sigs,
+                    name: sig.ident.to_string(),
+                    index,
+                    receiver,
+                });
+            }
+        }
+        self.sigs += 1;
+        visit::visit_signature(self, sig);
+    }
+}
+
+struct Apply {
+    target: Target,
+    current: usize,
+}
+
+impl VisitMut for Apply {
+    fn visit_signature_mut(&mut self, sig: &mut Signature) {
+        if self.current == self.target.sig {
+            remove_nth(&mut sig.inputs, self.target.index);
+        }
+        self.current += 1;
+        visit_mut::visit_signature_mut(self, sig);
+    }
+
+    fn visit_expr_call_mut(&mut self, call: &mut ExprCall) {
+        // `Type::method(receiver, ..)` passes the receiver as an argument,
+        // so the index is the same as in the signature.
+        if let Expr::Path(ExprPath { path, .. }) = &*call.func {
+            if names(path, &self.target.name) && self.target.index < call.args.len() {
+                remove_nth(&mut call.args, self.target.index);
+            }
+        }
+        visit_mut::visit_expr_call_mut(self, call);
+    }
+
+    fn visit_expr_method_call_mut(&mut self, call: &mut ExprMethodCall) {
+        if self.target.receiver && call.method == self.target.name {
+            let index = self.target.index - 1;
+            if index < call.args.len() {
+                remove_nth(&mut call.args, index);
+            }
+        }
+        visit_mut::visit_expr_method_call_mut(self, call);
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::HashSet;
+use std::mem;
+
+use proc_macro2::{TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::*;
+
+use super::Pass;
+
+/// Deletes, in a single candidate, every item of the file that cannot be
+/// reached from `main` or a public item.
+///
+/// Reachability is decided by a syntactic index: an item is used if one of
+/// the names it defines appears anywhere in an item that is used. This
+/// over-approximates uses (e.g. a local variable shadowing an item keeps
+/// it alive). Imports are always kept, as traits are often used through
+/// method calls only, which don't name them.
+pub struct UnusedItems;
+
+impl Pass for UnusedItems {
+    fn name(&self) -> &'static str {
+        "unused-items"
+    }
+
+    fn transform(&self, file: &mut File, n: usize) -> bool {
+        if n > 0 {
+            return false;
+        }
+
+        let keep = Index::new(&file.items).reachable();
+        if keep.iter().all(|&k| k) {
+            return false;
+        }
+
+        let mut keep = keep.into_iter();
+        file.items.retain(|_| keep.next().unwrap());
+        true
+    }
+}
+
+/// attributes that make an item used even when nothing names it.
+const ROOT_ATTRS: &[&str] = &[
+    "no_mangle",
+    "export_name",
+    "test",
+    "start",
+    "panic_handler",
+    "global_allocator",
+    "lang",
+    "used",
+];
+
+struct Def {
+    /// names defined by the item, empty for impls.
+    names: Vec<String>,
+    /// identifiers appearing anywhere in the item.
+    mentions: HashSet<String>,
+    kind: DefKind,
+}
+
+enum DefKind {
+    /// reachable regardless of uses
+    Root,
+    /// reachable once one of its names is used
+    Named,
+    /// Reachable once all of the items named by its self type are
+    /// reachable. Trait impls are often used without naming the trait,
+    /// so the trait is only considered for impls on foreign types.
+    Impl(HashSet<String>, HashSet<String>),
+}
+
+struct Index {
+    defs: Vec<Def>,
+}
+
+impl Index {
+    fn new(items: &[Item]) -> Self {
+        let mut defs: Vec<_> = items.iter().map(def).collect();
+
+        let defined: HashSet<_> = defs.iter().flat_map(|d| d.names.iter().cloned()).collect();
+        for d in &mut defs {
+            if let DefKind::Impl(key, tr) = &mut d.kind {
+                key.retain(|name| defined.contains(name));
+                if key.is_empty() {
+                    tr.retain(|name| defined.contains(name));
+                    *key = mem::take(tr);
+                }
+                if key.is_empty() {
+                    d.kind = DefKind::Root;
+                }
+            }
+        }
+
+        Self { defs }
+    }
+
+    /// whether each item is reachable from the roots.
+    fn reachable(&self) -> Vec<bool> {
+        let mut keep: Vec<_> = self
+            .defs
+            .iter()
+            .map(|d| matches!(d.kind, DefKind::Root))
+            .collect();
+        let mut used: HashSet<&str> = HashSet::new();
+        for (d, _) in self.defs.iter().zip(&keep).filter(|(_, &k)| k) {
+            used.extend(d.mentions.iter().map(String::as_str));
+        }
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (d, keep) in self.defs.iter().zip(&mut keep) {
+                if *keep {
+                    continue;
+                }
+                *keep = match &d.kind {
+                    DefKind::Root => true,
+                    DefKind::Named => d.names.iter().any(|n| used.contains(&**n)),
+                    DefKind::Impl(key, _) => key.iter().all(|n| used.contains(&**n)),
+                };
+                if *keep {
+                    used.extend(d.mentions.iter().map(String::as_str));
+                    changed = true;
+                }
+            }
+        }
+
+        keep
+    }
+}
+
+fn def(item: &Item) -> Def {
+    let mut mentions = HashSet::new();
+    idents(item.to_token_stream(), &mut mentions);
+
+    let (attrs, vis, names, kind) = match item {
+        Item::Const(i) => (&i.attrs, Some(&i.vis), vec![&i.ident], DefKind::Named),
+        Item::Enum(i) => (&i.attrs, Some(&i.vis), vec![&i.ident], DefKind::Named),
+        Item::ExternCrate(i) => {
+            let name = i.rename.as_ref().map_or(&i.ident, |(_, rename)| rename);
+            (&i.attrs, Some(&i.vis), vec![name], DefKind::Named)
+        }
+        Item::Fn(i) => {
+            let kind = if i.sig.ident == "main" {
+                DefKind::Root
+            } else {
+                DefKind::Named
+            };
+            (&i.attrs, Some(&i.vis), vec![&i.sig.ident], kind)
+        }
+        Item::Impl(i) => {
+            let mut key = HashSet::new();
+            idents(i.self_ty.to_token_stream(), &mut key);
+            let mut tr = HashSet::new();
+            if let Some((_, path, _)) = &i.trait_ {
+                idents(path.to_token_stream(), &mut tr);
+            }
+            (&i.attrs, None, vec![], DefKind::Impl(key, tr))
+        }
+        Item::Macro(i) => match &i.ident {
+            Some(ident) => (&i.attrs, None, vec![ident], DefKind::Named),
+            None => (&i.attrs, None, vec![], DefKind::Root),
+        },
+        Item::Macro2(i) => (&i.attrs, Some(&i.vis), vec![&i.ident], DefKind::Named),
+        Item::Mod(i) => (&i.attrs, Some(&i.vis), vec![&i.ident], DefKind::Named),
+        Item::Static(i) => (&i.attrs, Some(&i.vis), vec![&i.ident], DefKind::Named),
+        Item::Struct(i) => (&i.attrs, Some(&i.vis), vec![&i.ident], DefKind::Named),
+        Item::Trait(i) => (&i.attrs, Some(&i.vis), vec![&i.ident], DefKind::Named),
+        Item::TraitAlias(i) => (&i.attrs, Some(&i.vis), vec![&i.ident], DefKind::Named),
+        Item::Type(i) => (&i.attrs, Some(&i.vis), vec![&i.ident], DefKind::Named),
+        Item::Union(i) => (&i.attrs, Some(&i.vis), vec![&i.ident], DefKind::Named),
+        Item::Use(i) => (&i.attrs, Some(&i.vis), vec![], DefKind::Root),
+        // foreign modules and anything syn doesn't understand
+        _ => {
+            return Def {
+                names: vec![],
+                mentions,
+                kind: DefKind::Root,
+            }
+        }
+    };
+
+    let public = matches!(vis, Some(Visibility::Public(_)));
+    let kind = if public
+        || attrs
+            .iter()
+            .any(|a| ROOT_ATTRS.iter().any(|r| a.path.is_ident(r)))
+    {
+        DefKind::Root
+    } else {
+        kind
+    };
+
+    Def {
+        names: names.into_iter().map(Ident::to_string).collect(),
+        mentions,
+        kind,
+    }
+}
+
+/// collect all identifiers in the token stream, including those in macros.
+fn idents(ts: TokenStream, out: &mut HashSet<String>) {
+    for tt in ts {
+        match tt {
+            TokenTree::Ident(i) => {
+                out.insert(i.to_string());
+            }
+            TokenTree::Group(g) => idents(g.stream(), out),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use syn::visit::{self, Visit};
+use syn::visit_mut::{self, VisitMut};
+use syn::*;
+
+use super::{names, Pass};
+
+/// Removes an associated item from a trait, along with the item of the
+/// same name in every impl of that trait.
+pub struct TraitItems;
+
+impl Pass for TraitItems {
+    fn name(&self) -> &'static str {
+        "trait-items"
+    }
+
+    fn transform(&self, file: &mut File, n: usize) -> bool {
+        let mut collect = Collect::default();
+        collect.visit_file(file);
+
+        let (ordinal, index) = match collect.items.into_iter().nth(n) {
+            Some(target) => target,
+            None => return false,
+        };
+
+        let mut remove = RemoveTraitItem {
+            ordinal,
+            index,
+            current: 0,
+            removed: None,
+        };
+        remove.visit_file_mut(file);
+
+        // impls can come before the trait, so they are visited separately.
+        if let Some((tr, Some(item))) = remove.removed {
+            RemoveImplItems { tr, item }.visit_file_mut(file);
+        }
+
+        true
+    }
+}
+
+/// Turns a provided trait method into a required one by removing its body.
+pub struct DefaultBodies;
+
+impl Pass for DefaultBodies {
+    fn name(&self) -> &'static str {
+        "default-bodies"
+    }
+
+    fn transform(&self, file: &mut File, n: usize) -> bool {
+        let mut strip = StripBody { n, done: false };
+        strip.visit_file_mut(file);
+        strip.done
+    }
+}
+
+fn trait_item_name(item: &TraitItem) -> Option<&Ident> {
+    match item {
+        TraitItem::Const(c) => Some(&c.ident),
+        TraitItem::Method(m) => Some(&m.sig.ident),
+        TraitItem::Type(t) => Some(&t.ident),
+        _ => None,
+    }
+}
+
+fn impl_item_name(item: &ImplItem) -> Option<&Ident> {
+    match item {
+        ImplItem::Const(c) => Some(&c.ident),
+        ImplItem::Method(m) => Some(&m.sig.ident),
+        ImplItem::Type(t) => Some(&t.ident),
+        _ => None,
+    }
+}
+
+/// collects (trait, item) indices.
+#[derive(Default)]
+struct Collect {
+    traits: usize,
+    items: Vec<(usize, usize)>,
+}
+
+impl<'ast> Visit<'ast> for Collect {
+    fn visit_item_trait(&mut self, i: &'ast ItemTrait) {
+        self.items
+            .extend((0..i.items.len()).map(|index| (self.traits, index)));
+        self.traits += 1;
+        visit::visit_item_trait(self, i);
+    }
+}
+
+struct RemoveTraitItem {
+    /// index of the trait in visiting order
+    ordinal: usize,
+    index: usize,
+    current: usize,
+    /// name of the trait and of the removed item.
+    removed: Option<(Ident, Option<Ident>)>,
+}
+
+impl VisitMut for RemoveTraitItem {
+    fn visit_item_trait_mut(&mut self, i: &mut ItemTrait) {
+        if self.current == self.ordinal {
+            let item = i.items.remove(self.index);
+            self.removed = Some((i.ident.clone(), trait_item_name(&item).cloned()));
+        }
+        self.current += 1;
+        visit_mut::visit_item_trait_mut(self, i);
+    }
+}
+
+struct RemoveImplItems {
+    tr: Ident,
+    item: Ident,
+}
+
+impl VisitMut for RemoveImplItems {
+    fn visit_item_impl_mut(&mut self, i: &mut ItemImpl) {
+        if let Some((_, path, _)) = &i.trait_ {
+            if names(path, &self.tr.to_string()) {
+                i.items.retain(|it| impl_item_name(it) != Some(&self.item));
+            }
+        }
+        visit_mut::visit_item_impl_mut(self, i);
+    }
+}
+
+struct StripBody {
+    n: usize,
+    done: bool,
+}
+
+impl VisitMut for StripBody {
+    fn visit_trait_item_method_mut(&mut self, m: &mut TraitItemMethod) {
+        if !self.done && m.default.is_some() {
+            if self.n == 0 {
+                m.default = None;
+                m.semi_token = Some(Default::default());
+                self.done = true;
+            } else {
+                self.n -= 1;
+            }
+        }
+        visit_mut::visit_trait_item_method_mut(self, m);
+    }
+}
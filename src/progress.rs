@@ -0,0 +1,141 @@
+//! Statistics about a running reduction, see [`Reducer::progress`].
+//!
+//! [`Reducer::progress`]: crate::Reducer::progress
+
+use std::time::{Duration, Instant};
+
+pub type Callback = Box<dyn Fn(&Stats)>;
+
+/// a snapshot of the progress of a reduction.
+#[derive(Clone, Debug)]
+pub struct Stats {
+    /// tokens in the tree when the reduction started.
+    pub original_tokens: usize,
+    /// tokens in the best tree so far.
+    pub tokens: usize,
+    pub started: Instant,
+    /// round of the reduction, see [`Position`](crate::checkpoint::Position).
+    pub round: usize,
+    /// name of the running stage, `initial` while testing the input.
+    pub stage: String,
+    pub stage_started: Instant,
+    /// nodes reduced so far in the current tree stage.
+    pub nodes_done: usize,
+    /// nodes in the tree when the current tree stage started, zero in
+    /// other stages.
+    pub nodes_total: usize,
+    /// candidates tested by each stage, in the order they first ran.
+    pub stages: Vec<(String, StageStats)>,
+    /// every run of the checker, a candidate may be run several times
+    /// with [`Retry`](crate::Retry).
+    pub checker: CheckerTimes,
+    /// candidates on which the runs of the checker did not agree.
+    pub flaky: usize,
+    /// times the best tree was found to be no longer interesting and
+    /// rolled back, see [`Reducer::verify_interval`](crate::Reducer::verify_interval).
+    pub rollbacks: usize,
+}
+
+/// candidates tested by a stage over all rounds.
+#[derive(Clone, Debug, Default)]
+pub struct StageStats {
+    pub tried: usize,
+    /// candidates the checker found interesting.
+    pub accepted: usize,
+    /// time spent running the checker.
+    pub time: Duration,
+}
+
+/// how long the checker took to run.
+#[derive(Clone, Debug, Default)]
+pub struct CheckerTimes {
+    pub runs: usize,
+    pub total: Duration,
+    pub min: Duration,
+    pub max: Duration,
+    /// Number of runs by duration: bucket 0 counts runs under 1ms and
+    /// bucket `i` counts runs from 2^(i-1) up to 2^i ms.
+    pub buckets: Vec<usize>,
+}
+
+impl CheckerTimes {
+    pub fn mean(&self) -> Option<Duration> {
+        (self.runs > 0).then(|| self.total / self.runs as u32)
+    }
+
+    pub(crate) fn record(&mut self, time: Duration) {
+        self.min = if self.runs == 0 {
+            time
+        } else {
+            self.min.min(time)
+        };
+        self.max = self.max.max(time);
+        self.runs += 1;
+        self.total += time;
+
+        let ms = time.as_millis();
+        let bucket = (u128::BITS - ms.leading_zeros()) as usize;
+        if self.buckets.len() <= bucket {
+            self.buckets.resize(bucket + 1, 0);
+        }
+        self.buckets[bucket] += 1;
+    }
+}
+
+impl Stats {
+    pub fn new(tokens: usize) -> Self {
+        let now = Instant::now();
+        Self {
+            original_tokens: tokens,
+            tokens,
+            started: now,
+            round: 0,
+            stage: "initial".to_owned(),
+            stage_started: now,
+            nodes_done: 0,
+            nodes_total: 0,
+            stages: Vec::new(),
+            checker: CheckerTimes::default(),
+            flaky: 0,
+            rollbacks: 0,
+        }
+    }
+
+    /// record that `stage` of `round` is starting on a tree of `nodes`
+    /// nodes, zero if the stage does not go through the tree.
+    pub(crate) fn enter(&mut self, round: usize, stage: &str, nodes: usize) {
+        self.round = round;
+        self.stage = stage.to_owned();
+        self.stage_started = Instant::now();
+        self.nodes_done = 0;
+        self.nodes_total = nodes;
+    }
+
+    /// record a candidate tested by the current stage, in `time` over all
+    /// runs of the checker.
+    pub(crate) fn record(&mut self, time: Duration, accepted: bool) {
+        let stats = match self.stages.iter().position(|(name, _)| *name == self.stage) {
+            Some(i) => &mut self.stages[i].1,
+            None => {
+                self.stages
+                    .push((self.stage.clone(), StageStats::default()));
+                &mut self.stages.last_mut().unwrap().1
+            }
+        };
+        stats.tried += 1;
+        stats.accepted += usize::from(accepted);
+        stats.time += time;
+    }
+
+    /// Estimated time until the current tree stage is finished,
+    /// extrapolated from the nodes reduced so far.
+    ///
+    /// This overestimates, as nodes below deleted ones are never reduced.
+    pub fn remaining(&self) -> Option<Duration> {
+        if self.nodes_done == 0 || self.nodes_total <= self.nodes_done {
+            return None;
+        }
+        let per_node = self.stage_started.elapsed() / self.nodes_done as u32;
+        Some(per_node * (self.nodes_total - self.nodes_done) as u32)
+    }
+}
@@ -0,0 +1,63 @@
+//! A summary of what each strategy achieved, returned by
+//! [`Reducer::reduce`](crate::Reducer::reduce).
+//!
+//! With the `serde` feature the report can be serialized, with durations
+//! as seconds.
+
+use std::time::Duration;
+
+/// the strategies of a whole reduction.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Report {
+    pub original_tokens: usize,
+    pub tokens: usize,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "secs"))]
+    pub time: Duration,
+    /// in the order they first ran.
+    pub strategies: Vec<Strategy>,
+}
+
+/// A way of reducing the program: deleting optional nodes, minimizing
+/// kleene nodes, replacing nodes by a [`ReplacementRule`], or a whole-file
+/// [`Pass`].
+///
+/// [`ReplacementRule`]: crate::ReplacementRule
+/// [`Pass`]: crate::passes::Pass
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Strategy {
+    /// `optional`, `kleene/<minimizer>`, `replace/<rule>` or the name of
+    /// the pass.
+    pub name: String,
+    /// candidates tested.
+    pub tried: usize,
+    /// candidates the checker found interesting.
+    pub accepted: usize,
+    /// negative if the strategy made the program larger, e.g. inlining.
+    pub tokens_removed: isize,
+    /// time spent in the strategy, including running the checker.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "secs"))]
+    pub time: Duration,
+}
+
+impl Report {
+    /// index of the strategy called `name`, adding it if needed.
+    pub(crate) fn strategy(&mut self, name: &str) -> usize {
+        match self.strategies.iter().position(|s| s.name == name) {
+            Some(i) => i,
+            None => {
+                self.strategies.push(Strategy {
+                    name: name.to_owned(),
+                    ..Strategy::default()
+                });
+                self.strategies.len() - 1
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+fn secs<S: serde::Serializer>(d: &Duration, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_f64(d.as_secs_f64())
+}
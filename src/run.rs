@@ -0,0 +1,103 @@
+use std::cmp;
+use std::io::{self, Read, Seek};
+use std::process::{Command, ExitStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use shared_child::SharedChild;
+
+/// how often a running child is killed at the latest after cancelling.
+const CANCEL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// what a command printed, and how it exited.
+pub(crate) struct Finished {
+    /// `None` if it was killed after running out of time.
+    pub(crate) status: Option<ExitStatus>,
+    pub(crate) stdout: Vec<u8>,
+    pub(crate) stderr: Vec<u8>,
+}
+
+/// Run the command, capturing its output, and kill it once it runs longer
+/// than `timeout`. Fails with [`io::ErrorKind::Interrupted`] when `cancel`
+/// is set while it runs.
+pub(crate) fn run(
+    command: &mut Command,
+    timeout: Option<Duration>,
+    cancel: &AtomicBool,
+) -> io::Result<Finished> {
+    // files rather than pipes, so the output can't fill up a
+    // pipe while we are waiting.
+    let mut stdout = tempfile::tempfile()?;
+    let mut stderr = tempfile::tempfile()?;
+    command
+        .stdout(stdout.try_clone()?)
+        .stderr(stderr.try_clone()?);
+
+    let status = wait(&SharedChild::spawn(command)?, timeout, cancel)?;
+    let mut finished = Finished {
+        status,
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+    };
+    stdout.rewind()?;
+    stdout.read_to_end(&mut finished.stdout)?;
+    stderr.rewind()?;
+    stderr.read_to_end(&mut finished.stderr)?;
+    Ok(finished)
+}
+
+/// why the watchdog of a child killed it.
+enum Killed {
+    Cancelled,
+    TimedOut,
+}
+
+/// Wait for the child to exit, killing it if `cancel` is set. Returns
+/// `None` if it was killed after running longer than `timeout`.
+fn wait(
+    child: &SharedChild,
+    timeout: Option<Duration>,
+    cancel: &AtomicBool,
+) -> io::Result<Option<ExitStatus>> {
+    let deadline = timeout.map(|t| Instant::now() + t);
+    let (exited, watch) = mpsc::channel::<()>();
+
+    thread::scope(|scope| {
+        // blocking in `wait` notices the exit right away, so only the
+        // watchdog wakes up now and then to check for cancellation.
+        let watchdog = scope.spawn(move || -> io::Result<Option<Killed>> {
+            loop {
+                let mut wake = CANCEL_INTERVAL;
+                if let Some(deadline) = deadline {
+                    wake = cmp::min(wake, deadline.saturating_duration_since(Instant::now()));
+                }
+                if let Err(RecvTimeoutError::Disconnected) = watch.recv_timeout(wake) {
+                    return Ok(None);
+                }
+                let killed = if cancel.load(Ordering::Relaxed) {
+                    Killed::Cancelled
+                } else if deadline.is_some_and(|d| Instant::now() >= d) {
+                    Killed::TimedOut
+                } else {
+                    continue;
+                };
+                child.kill()?;
+                return Ok(Some(killed));
+            }
+        });
+
+        let status = child.wait();
+        drop(exited);
+        let killed = watchdog.join().unwrap()?;
+        match (status?, killed) {
+            (_, Some(Killed::Cancelled)) => Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "the reduction was cancelled",
+            )),
+            (_, Some(Killed::TimedOut)) => Ok(None),
+            (status, None) => Ok(Some(status)),
+        }
+    })
+}
@@ -1,13 +1,19 @@
-use crate::delta_debugging::{ddmin, Criteria};
+use crate::checkpoint::{self, Position};
+use crate::dd::{ddmin, is_one_minimal, Criteria, Minimizer};
+use crate::lower::Lower;
+use crate::passes::*;
+use crate::{Node, NodeKind, ReduceRule, Reducer, Schedule};
+
+use quote::ToTokens;
 
 pub struct TestCriteria<F>(F);
 
 impl<F: FnMut(Vec<T>) -> bool, T: Copy> Criteria<T> for TestCriteria<F> {
-    fn passes<'a, I: Iterator<Item = &'a T>>(&mut self, iter: I) -> bool
+    fn passes<'a, I: IntoIterator<Item = &'a T>>(&mut self, iter: I) -> bool
     where
         T: 'a,
     {
-        self.0(iter.copied().collect())
+        self.0(iter.into_iter().copied().collect())
     }
 }
 
@@ -42,3 +48,1455 @@ fn test_delta_debugging() {
     // there is a zero
     assert_eq!(vec![0], case(&input, |v| v.iter().any(|&v| v == 0)));
 }
+
+/// a reducer for `src` with a checker accepting files that parse and
+/// contain all of `needles`, ignoring whitespace.
+fn reducer(src: &str, needles: &'static [&'static str]) -> Reducer {
+    let file = syn::parse_file(src).unwrap();
+    Reducer::new(
+        file.lower(),
+        ReduceRule::Fn(Box::new(move |tmp| {
+            let s = std::fs::read_to_string(tmp.path()).unwrap();
+            let compact: String = s.split_whitespace().collect();
+            needles.iter().all(|n| compact.contains(n)) && syn::parse_file(&s).is_ok()
+        })),
+    )
+}
+
+fn reduce(src: &str, needles: &'static [&'static str], schedule: Schedule) -> Reducer {
+    let mut reducer = reducer(src, needles);
+    reducer.schedule = schedule;
+    reducer.reduce().unwrap();
+    reducer
+}
+
+/// the token count of the tree must match lowering its output again.
+#[track_caller]
+fn assert_tokens(reducer: &Reducer, expected: usize) {
+    let fresh = syn::parse_file(&reducer.root.to_string())
+        .unwrap()
+        .lower()
+        .tokens();
+    assert_eq!(fresh, expected, "{}", reducer.root);
+    assert_eq!(reducer.root.tokens(), expected, "{}", reducer.root);
+}
+
+const SCHEDULES: [Schedule; 3] = [
+    Schedule::DepthFirst,
+    Schedule::Levels,
+    Schedule::LargestFirst,
+];
+
+#[test]
+fn tokens_after_optional_deletion() {
+    for schedule in SCHEDULES {
+        let reducer = reduce("pub fn main() -> u8 { 0 }", &["fnmain", "0"], schedule);
+        // fn main ( ) { 0 }
+        assert_tokens(&reducer, 7);
+    }
+}
+
+#[test]
+fn tokens_after_delta_debugging() {
+    for schedule in SCHEDULES {
+        let reducer = reduce("fn main() { a(); b(); c(); }", &["b()"], schedule);
+        // fn main ( ) { b ( ) ; }
+        assert_tokens(&reducer, 10);
+    }
+}
+
+#[test]
+fn tokens_after_replacement() {
+    for schedule in SCHEDULES {
+        let reducer = reduce(
+            "fn main() { let x = (1 + 2) * 3; }",
+            &["letx=", "2"],
+            schedule,
+        );
+        // fn main ( ) { let x = 2 ; }
+        assert_tokens(&reducer, 11);
+    }
+}
+
+#[test]
+fn tokens_after_whole_file_pass() {
+    for schedule in SCHEDULES {
+        let reducer = reduce(
+            "struct S<T>(u8); fn main() { let s: S<u8> = S(0); }",
+            &["structS", "lets:S", "=S"],
+            schedule,
+        );
+        // struct S < > ; fn main ( ) { let s : S < > = S ; }
+        assert_tokens(&reducer, 20);
+    }
+}
+
+#[test]
+fn passes_only_shrink() {
+    // inlining into every use is interesting, but larger.
+    let many = reducer(
+        "const C: u8 = 1 + 2 + 3; fn main() { let a = C; let b = C; let c = C; }",
+        &[],
+    );
+    assert!(!many.run_pass(&InlineItems).unwrap());
+
+    let once = reducer("const C: u8 = 1 + 2 + 3; fn main() { let a = C; }", &[]);
+    assert!(once.run_pass(&InlineItems).unwrap());
+    assert!(!once.root.to_string().contains("const"));
+}
+
+/// Reduce `src` like [`reduce`] without passes, returning the candidates
+/// tested in order.
+fn candidates(src: &str, needles: &'static [&'static str], schedule: Schedule) -> Vec<String> {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let tested = Rc::new(RefCell::new(Vec::new()));
+    let record = tested.clone();
+    let mut reducer = Reducer::new(
+        syn::parse_file(src).unwrap().lower(),
+        ReduceRule::Source(Box::new(move |s| {
+            record
+                .borrow_mut()
+                .push(s.split_whitespace().collect::<String>());
+            let compact: String = s.split_whitespace().collect();
+            needles.iter().all(|n| compact.contains(n)) && syn::parse_file(s).is_ok()
+        })),
+    );
+    reducer.schedule = schedule;
+    reducer.passes.clear();
+    reducer.reduce().unwrap();
+    tested.take()
+}
+
+#[test]
+fn levels_reduce_shallow_nodes_first() {
+    let src = "fn a() { if c { if d { x(); w(); } } } fn b() { y(); z(); } fn main() { a(); b(); }";
+    let needles = &["ifc{ifd{x();", "fnb(){y();", "fnmain"];
+    // removing `w();` is deep in `a`, removing `z();` is shallow in `b`.
+    let first = |tested: &[String], removed: &str, kept: &str| {
+        tested
+            .iter()
+            .position(|c| !c.contains(removed) && c.contains(kept))
+            .unwrap()
+    };
+
+    let tested = candidates(src, needles, Schedule::Levels);
+    assert!(first(&tested, "z();", "fnb(){y();") < first(&tested, "w();", "ifd{x();"));
+
+    // depth first finishes `a` before getting to `b`.
+    let tested = candidates(src, needles, Schedule::DepthFirst);
+    assert!(first(&tested, "z();", "fnb(){y();") > first(&tested, "w();", "ifd{x();"));
+}
+
+#[test]
+fn replacement_depth() {
+    // only unwrapping `x` all at once is interesting.
+    let reducer = || {
+        Reducer::new(
+            syn::parse_file("fn main() { let v = w(w(w(w(w(x))))); }")
+                .unwrap()
+                .lower(),
+            ReduceRule::Source(Box::new(|s| {
+                let wraps = s.matches("w (").count();
+                s.contains('x') && (wraps == 0 || wraps == 5) && syn::parse_file(s).is_ok()
+            })),
+        )
+    };
+
+    let shallow = reducer();
+    shallow.reduce().unwrap();
+    assert_eq!(
+        shallow.root.to_string(),
+        "fn main ( ) { let v = w ( w ( w ( w ( w ( x ) ) ) ) ) ; } "
+    );
+
+    let mut deep = reducer();
+    deep.replace_depth = 20;
+    deep.reduce().unwrap();
+    assert_eq!(deep.root.to_string(), "fn main ( ) { let v = x ; } ");
+}
+
+/// Apply the `n`th transformation of `pass` to `src`, which must give
+/// `expected`, or nothing if it is `None`.
+#[track_caller]
+fn assert_pass(pass: &dyn Pass, src: &str, n: usize, expected: Option<&str>) {
+    let mut file = syn::parse_file(src).unwrap();
+    let changed = pass.transform(&mut file, n);
+    let found = file.to_token_stream().to_string();
+    let expected = expected.map(|e| syn::parse_file(e).unwrap().to_token_stream().to_string());
+    assert_eq!(changed.then_some(found.clone()), expected);
+    if !changed {
+        let original = syn::parse_file(src).unwrap().to_token_stream().to_string();
+        assert_eq!(found, original, "changed without a transformation");
+    }
+}
+
+#[test]
+fn generic_params() {
+    assert_pass(
+        &GenericParams,
+        "struct S<T>(u8); impl<T> S<T> { fn f(&self) {} } fn g(s: S<u8>) {}",
+        0,
+        Some("struct S(u8); impl S { fn f(&self) {} } fn g(s: S) {}"),
+    );
+    // lifetimes elided at the use are skipped.
+    assert_pass(
+        &GenericParams,
+        "struct S<'a, T>(&'a T); fn f(s: S<u8>) {}",
+        1,
+        Some("struct S<'a>(&'a T); fn f(s: S) {}"),
+    );
+    assert_pass(
+        &GenericParams,
+        "struct S<T, U>(U) where T: Copy; impl<T: Copy, U> S<T, U> where T: Clone {}",
+        0,
+        Some("struct S<U>(U); impl<U> S<U> {}"),
+    );
+    // the lifetime was never constrained by the self type.
+    assert_pass(
+        &GenericParams,
+        "struct S<T>(u8); impl<'a, T> S<T> { fn f(&'a self) {} }",
+        0,
+        Some("struct S(u8); impl<'a> S { fn f(&'a self) {} }"),
+    );
+    assert_pass(
+        &GenericParams,
+        "trait Tr<T> {} struct S<T>(T); impl<T> Tr<T> for S<T> {}",
+        0,
+        Some("trait Tr {} struct S<T>(T); impl<T> Tr for S<T> {}"),
+    );
+    assert_pass(
+        &GenericParams,
+        "fn f<T>() {} fn main() { f::<u8>(); }",
+        0,
+        Some("fn f() {} fn main() { f(); }"),
+    );
+    assert_pass(&GenericParams, "fn f<T>() {} fn main() {}", 1, None);
+}
+
+#[test]
+fn fn_params() {
+    assert_pass(
+        &FnParams,
+        "fn f(a: u8, b: u8) {} fn main() { f(1, 2); }",
+        0,
+        Some("fn f(b: u8) {} fn main() { f(2); }"),
+    );
+    // method calls pass the receiver before the arguments.
+    assert_pass(
+        &FnParams,
+        "struct S; impl S { fn m(&self, a: u8, b: u8) {} } fn main() { S.m(1, 2); S::m(&S, 1, 2); }",
+        1,
+        Some("struct S; impl S { fn m(&self, a: u8) {} } fn main() { S.m(1); S::m(&S, 1); }"),
+    );
+    // the receiver itself is never removed.
+    assert_pass(&FnParams, "struct S; impl S { fn m(&self) {} }", 0, None);
+}
+
+#[test]
+fn struct_fields() {
+    assert_pass(
+        &StructFields,
+        "struct P { x: u8, y: u8 } fn f(p: P) -> u8 { let P { x, y } = p; P { x: 1, y: 2 }; p.x }",
+        1,
+        Some("struct P { x: u8 } fn f(p: P) -> u8 { let P { x } = p; P { x: 1 }; p.x }"),
+    );
+    assert_pass(
+        &StructFields,
+        "enum E { A(u8, u8) } fn f() { E::A(1, 2); }",
+        0,
+        Some("enum E { A(u8) } fn f() { E::A(2); }"),
+    );
+    // fields after `..` are counted from the end, so those are left alone.
+    assert_pass(
+        &StructFields,
+        "struct S(u8, u8, u8); fn f(s: S) { let S(a, ..) = s; let S(.., c) = s; S(1, 2, 3); }",
+        0,
+        Some("struct S(u8, u8); fn f(s: S) { let S(..) = s; let S(.., c) = s; S(2, 3); }"),
+    );
+    assert_pass(
+        &StructFields,
+        "struct S(u8, u8, u8); fn f(s: S) { let S(a, ..) = s; S(1, 2, 3); }",
+        2,
+        Some("struct S(u8, u8); fn f(s: S) { let S(a, ..) = s; S(1, 2); }"),
+    );
+}
+
+#[test]
+fn struct_fields_through_self() {
+    assert_pass(
+        &StructFields,
+        "struct P { x: u8, y: u8 } \
+         impl P { fn new() -> Self { Self { x: 1, y: 2 } } fn x(self) -> u8 { let Self { x, y } = self; x } } \
+         impl Q { fn new() -> Self { Self { x: 1, y: 2 } } }",
+        1,
+        Some(
+            "struct P { x: u8 } \
+             impl P { fn new() -> Self { Self { x: 1 } } fn x(self) -> u8 { let Self { x } = self; x } } \
+             impl Q { fn new() -> Self { Self { x: 1, y: 2 } } }",
+        ),
+    );
+    assert_pass(
+        &StructFields,
+        "struct T(u8, u8); impl T { fn new() -> Self { Self(1, 2) } }",
+        0,
+        Some("struct T(u8); impl T { fn new() -> Self { Self(2) } }"),
+    );
+    assert_pass(
+        &UnitStructs,
+        "struct T(u8); impl Default for T { fn default() -> Self { Self(1) } }",
+        0,
+        Some("struct T; impl Default for T { fn default() -> Self { Self } }"),
+    );
+}
+
+#[test]
+fn unit_structs() {
+    assert_pass(
+        &UnitStructs,
+        "struct S(u8); fn f() -> S { S(1) }",
+        0,
+        Some("struct S; fn f() -> S { S }"),
+    );
+    assert_pass(
+        &UnitStructs,
+        "struct S(u8); enum E { A(u8), B } fn f(e: E) { match e { E::A(x) => {} E::B => {} } }",
+        1,
+        Some("struct S(u8); enum E { A, B } fn f(e: E) { match e { E::A => {} E::B => {} } }"),
+    );
+    // named fields are left to `StructFields`.
+    assert_pass(&UnitStructs, "struct S { x: u8 } struct T;", 0, None);
+}
+
+#[test]
+fn variants() {
+    let src = "enum E { A, B, C } fn f(e: E) -> u8 { match e { E::A | E::B => 1, E::C => 2 } }";
+    assert_pass(
+        &Variants,
+        src,
+        1,
+        Some("enum E { A, C } fn f(e: E) -> u8 { match e { E::A => 1, E::C => 2 } }"),
+    );
+    assert_pass(
+        &Variants,
+        src,
+        2,
+        Some("enum E { A, B } fn f(e: E) -> u8 { match e { E::A | E::B => 1, } }"),
+    );
+    assert_pass(&Variants, src, 3, None);
+    // `Self::` in the impl names the same variant.
+    assert_pass(
+        &Variants,
+        "enum E { A, B } impl E { fn f(&self) { match self { Self::A => {} _ => {} } } }",
+        0,
+        Some("enum E { B } impl E { fn f(&self) { match self { _ => {} } } }"),
+    );
+}
+
+#[test]
+fn trait_items() {
+    // the impl comes before the trait.
+    assert_pass(
+        &TraitItems,
+        "impl Tr for u8 { type T = u8; fn f(&self) {} } trait Tr { type T; fn f(&self); } \
+         impl Other for u8 { fn f(&self) {} }",
+        1,
+        Some(
+            "impl Tr for u8 { type T = u8; } trait Tr { type T; } \
+             impl Other for u8 { fn f(&self) {} }",
+        ),
+    );
+    assert_pass(
+        &TraitItems,
+        "trait A { const C: u8; } trait B { fn f(); } impl B for () { fn f() {} }",
+        1,
+        Some("trait A { const C: u8; } trait B {} impl B for () {}"),
+    );
+    assert_pass(&TraitItems, "trait A { fn f(); }", 1, None);
+}
+
+#[test]
+fn default_bodies() {
+    let src = "trait A { fn f() {} fn g(); fn h() {} }";
+    assert_pass(
+        &DefaultBodies,
+        src,
+        1,
+        Some("trait A { fn f() {} fn g(); fn h(); }"),
+    );
+    assert_pass(&DefaultBodies, src, 2, None);
+}
+
+#[test]
+fn unused_items() {
+    // the trait is only used through a method call.
+    assert_pass(
+        &UnusedItems,
+        "use std::io::Write; fn main() { Vec::new().write_all(b\"\").unwrap(); } fn dead() {}",
+        0,
+        Some("use std::io::Write; fn main() { Vec::new().write_all(b\"\").unwrap(); }"),
+    );
+    assert_pass(
+        &UnusedItems,
+        "struct S; impl S { fn f() {} } struct T; impl T {} impl Clone for T { fn clone(&self) -> T { T } } \
+         fn main() { S::f(); }",
+        0,
+        Some("struct S; impl S { fn f() {} } fn main() { S::f(); }"),
+    );
+    // impls on foreign types are kept as long as the trait is.
+    assert_pass(
+        &UnusedItems,
+        "trait Tr { fn tr(&self); } impl Tr for u8 { fn tr(&self) {} } trait Un {} impl Un for u8 {} \
+         fn main() { Tr::tr(&1u8); }",
+        0,
+        Some("trait Tr { fn tr(&self); } impl Tr for u8 { fn tr(&self) {} } fn main() { Tr::tr(&1u8); }"),
+    );
+    assert_pass(
+        &UnusedItems,
+        "macro_rules! m { () => { helper() } } fn helper() {} macro_rules! unused { () => {} } \
+         fn main() { m!(); }",
+        0,
+        Some("macro_rules! m { () => { helper() } } fn helper() {} fn main() { m!(); }"),
+    );
+    assert_pass(
+        &UnusedItems,
+        "mod a { pub fn f() {} } mod b {} use a::*; fn main() { f(); }",
+        0,
+        Some("mod a { pub fn f() {} } use a::*; fn main() { f(); }"),
+    );
+    assert_pass(
+        &UnusedItems,
+        "#[no_mangle] fn exported() { helper() } fn helper() {} fn dead() {} pub fn api() {}",
+        0,
+        Some("#[no_mangle] fn exported() { helper() } fn helper() {} pub fn api() {}"),
+    );
+    assert_pass(&UnusedItems, "fn main() {}", 0, None);
+    assert_pass(&UnusedItems, "fn main() {} fn dead() {}", 1, None);
+}
+
+#[test]
+fn inline_fns() {
+    // the arguments name the parameters the other way around.
+    assert_pass(
+        &InlineFns,
+        "fn f(a: u8, b: u8) -> u8 { a - b } fn main() { let (a, b) = (1, 2); f(b, a); }",
+        0,
+        Some("fn main() { let (a, b) = (1, 2); { let (a, b): (u8, u8) = (b, a); a - b }; }"),
+    );
+    assert_pass(
+        &InlineFns,
+        "fn f(a: u8) -> u8 { a + 1 } fn main() { f(2); }",
+        0,
+        Some("fn main() { { let a: u8 = 2; a + 1 }; }"),
+    );
+    assert_pass(
+        &InlineFns,
+        "fn f() { g(); } fn main() { f(); }",
+        0,
+        Some("fn main() { { g(); }; }"),
+    );
+
+    // the only use is not a call.
+    assert_pass(
+        &InlineFns,
+        "fn f() {} fn main() { let g = f; g(); }",
+        0,
+        None,
+    );
+    assert_pass(&InlineFns, "fn f(a: u8) {} fn main() { f(1, 2); }", 0, None);
+    assert_pass(&InlineFns, "fn f() {} fn main() { f(); f(); }", 0, None);
+    assert_pass(
+        &InlineFns,
+        "fn f() -> u8 { return 1; } fn main() { f(); }",
+        0,
+        None,
+    );
+}
+
+/// xorshift, to test with many different criteria deterministically.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 as usize
+    }
+}
+
+#[test]
+fn ddmin_is_one_minimal() {
+    let mut rng = Rng(0x2545f4914f6cdd1d);
+    for _ in 0..500 {
+        let len = rng.next() % 24 + 1;
+        let input: Vec<usize> = (0..len).map(|_| rng.next() % 10).collect();
+
+        // not monotone: adding items can make it fail again.
+        let modulus = rng.next() % 5 + 2;
+        let remainder = input.iter().sum::<usize>() % modulus;
+        let required = input[rng.next() % len];
+        let f = move |v: Vec<usize>| {
+            v.contains(&required) && v.iter().sum::<usize>() % modulus == remainder
+        };
+
+        let mut out = input.clone();
+        ddmin(&mut out, &mut TestCriteria(f));
+
+        assert!(f(out.clone()), "{input:?} -> {out:?} fails");
+        assert!(
+            is_one_minimal(&out, &mut TestCriteria(f)),
+            "{input:?} -> {out:?} is not 1-minimal"
+        );
+    }
+}
+
+#[test]
+fn test_is_one_minimal() {
+    let mut has_zero = TestCriteria(|v: Vec<usize>| v.contains(&0));
+    assert!(is_one_minimal(&[0], &mut has_zero));
+    assert!(!is_one_minimal(&[0, 1], &mut has_zero));
+    // 1-minimal does not mean minimal, either zero can be removed alone
+    // but not both.
+    assert!(!is_one_minimal(&[0, 0], &mut has_zero));
+
+    let mut even_len = TestCriteria(|v: Vec<usize>| v.len().is_multiple_of(2));
+    assert!(is_one_minimal(&[1, 2], &mut even_len));
+}
+
+/// whether `sub` can be obtained by removing items from `items`.
+fn is_subsequence(sub: &[usize], items: &[usize]) -> bool {
+    let mut items = items.iter();
+    sub.iter().all(|s| items.any(|i| i == s))
+}
+
+#[test]
+fn test_minimizers() {
+    type Case = (&'static str, fn(Vec<usize>) -> bool, Option<Vec<usize>>);
+    let cases: [Case; 6] = [
+        ("zero", |v| v.contains(&0), Some(vec![0])),
+        (
+            "ten and thirty-five",
+            |v| v.contains(&10) && v.contains(&35),
+            Some(vec![10, 35]),
+        ),
+        (
+            "two odd numbers",
+            |v| v.iter().filter(|&v| v % 2 == 1).count() == 2,
+            Some(vec![25, 35]),
+        ),
+        ("sum at least 60", |v| v.iter().sum::<usize>() >= 60, None),
+        ("even length", |v| v.len() % 2 == 0, None),
+        (
+            "average 20",
+            |v| !v.is_empty() && v.iter().sum::<usize>() / v.len() == 20,
+            None,
+        ),
+    ];
+
+    let input: Vec<usize> = vec![10, 20, 10, 30, 25, 35, 0, 30];
+
+    for minimizer in Minimizer::ALL {
+        for (name, f, expected) in &cases {
+            let mut out = input.clone();
+            minimizer.minimize(&mut out, &mut TestCriteria(f));
+
+            assert!(f(out.clone()), "{minimizer:?} {name}: {out:?} fails");
+            assert!(
+                is_subsequence(&out, &input),
+                "{minimizer:?} {name}: {out:?} is not a subsequence"
+            );
+            if let Some(expected) = expected {
+                assert_eq!(expected, &out, "{minimizer:?} {name}");
+            }
+        }
+    }
+}
+
+#[test]
+fn tokens_with_minimizers() {
+    for minimizer in Minimizer::ALL {
+        let mut reducer = reducer("fn main() { a(); b(); c(); d(); }", &["b()", "d()"]);
+        reducer.minimizer = minimizer;
+        reducer.reduce().unwrap();
+        // fn main ( ) { b ( ) ; d ( ) ; }
+        assert_tokens(&reducer, 14);
+    }
+}
+
+#[test]
+fn checkpoint_roundtrip() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("checkpoint.rs");
+    let position = Position {
+        round: 3,
+        stage: "struct-fields".to_owned(),
+    };
+
+    checkpoint::save(&path, &position, "fn main ( ) { }").unwrap();
+    let (loaded, source) = checkpoint::load(&path).unwrap();
+    assert_eq!(loaded, position);
+    assert!(syn::parse_file(&source).is_ok());
+
+    std::fs::write(&path, "fn main() {}").unwrap();
+    assert!(checkpoint::load(&path).is_err());
+}
+
+#[test]
+fn resume_from_checkpoint() {
+    const SRC: &str = "struct S<T>(u8); fn main() { let s: S<u8> = S(0); a(); }";
+    const NEEDLES: &[&str] = &["structS", "lets:S", "=S"];
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("checkpoint.rs");
+
+    let mut full = reducer(SRC, NEEDLES);
+    full.checkpoint = Some(path.clone());
+    full.reduce().unwrap();
+
+    let (position, source) = checkpoint::load(&path).unwrap();
+    assert_eq!(position.stage, checkpoint::DONE);
+    assert_eq!(source.trim(), full.root.to_string().trim());
+
+    // pretend the reduction was stopped before running the passes.
+    let mut resumed = reducer(SRC, NEEDLES);
+    resumed.checkpoint = Some(path.clone());
+    resumed
+        .reduce_from(Position {
+            round: 0,
+            stage: "generic-params".to_owned(),
+        })
+        .unwrap();
+    // the skipped tree stage must run in the following round.
+    let (position, source) = checkpoint::load(&path).unwrap();
+    assert_eq!(position.stage, checkpoint::DONE);
+    assert!(position.round > 0);
+    assert!(!source.contains("a ("), "{source}");
+}
+
+#[test]
+fn cancel_keeps_best_result() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    const SRC: &str = "fn main() { a(); b(); c(); d(); e(); }";
+
+    for calls in 1..8 {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checkpoint.rs");
+        let cancel = Arc::new(AtomicBool::new(false));
+
+        // cancel while the checker is running its `calls`th test.
+        let count = std::cell::Cell::new(0);
+        let flag = cancel.clone();
+        let file = syn::parse_file(SRC).unwrap();
+        let mut reducer = Reducer::new(
+            file.lower(),
+            ReduceRule::Fn(Box::new(move |tmp| {
+                count.set(count.get() + 1);
+                if count.get() == calls {
+                    flag.store(true, Ordering::Relaxed);
+                }
+                let s = std::fs::read_to_string(tmp.path()).unwrap();
+                s.contains("c (") && syn::parse_file(&s).is_ok()
+            })),
+        );
+        reducer.cancel = cancel;
+        reducer.checkpoint = Some(path.clone());
+
+        let err = reducer.reduce().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Interrupted);
+
+        // no temporary replacement is left in the tree.
+        let best = reducer.root.to_string();
+        assert!(best.contains("c ("), "{best}");
+        assert!(syn::parse_file(&best).is_ok(), "{best}");
+        assert_tokens(&reducer, reducer.root.tokens());
+
+        let (_, source) = checkpoint::load(&path).unwrap();
+        assert_eq!(source.trim(), best.trim());
+    }
+}
+
+#[test]
+fn largest_first_is_anytime() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    const SRC: &str = "fn small() { a(); h(); } fn big() { b(); c(); d(); e(); f(); g(); } \
+                       fn main() { small(); big(); }";
+    const SMALL: &str = "fnsmall(){a();h();}";
+    const BIG: &str = "fnbig(){b();c();d();e();f();g();}";
+    // the input, the candidates deleting whole items, and a few more.
+    const CHECKS: usize = 10;
+
+    for (schedule, first, second) in [
+        (Schedule::LargestFirst, BIG, SMALL),
+        (Schedule::DepthFirst, SMALL, BIG),
+    ] {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let flag = cancel.clone();
+        let tested = Rc::new(RefCell::new(Vec::new()));
+        let record = tested.clone();
+        let mut reducer = Reducer::new(
+            syn::parse_file(SRC).unwrap().lower(),
+            ReduceRule::Source(Box::new(move |s| {
+                let compact: String = s.split_whitespace().collect();
+                let mut tested = record.borrow_mut();
+                tested.push(compact.clone());
+                if tested.len() == CHECKS {
+                    flag.store(true, Ordering::Relaxed);
+                }
+                [
+                    "fnsmall(){a();",
+                    "fnbig(){",
+                    "g();}",
+                    "fnmain(){small();big();}",
+                ]
+                .iter()
+                .all(|n| compact.contains(n))
+                    && syn::parse_file(s).is_ok()
+            })),
+        );
+        reducer.schedule = schedule;
+        reducer.cancel = cancel;
+
+        let err = reducer.reduce().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Interrupted);
+
+        // below the items, only the first function was tried so far.
+        let tested = tested.take();
+        assert_eq!(tested.len(), CHECKS);
+        let inside: Vec<_> = tested[1..]
+            .iter()
+            .filter(|c| ["fnsmall", "fnbig", "fnmain"].iter().all(|n| c.contains(n)))
+            .collect();
+        assert!(!inside.is_empty(), "{schedule:?}");
+        for c in inside {
+            assert!(
+                !c.contains(first) && c.contains(second),
+                "{schedule:?}: {c}"
+            );
+        }
+    }
+}
+
+#[test]
+fn progress_stats() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let mut reducer = reducer("fn main() { a(); b(); c(); }", &["b()"]);
+    let reports = Rc::new(Cell::new(0));
+    let counter = reports.clone();
+    reducer.progress = Some(Box::new(move |stats| {
+        counter.set(counter.get() + 1);
+        assert!(stats.nodes_done <= stats.nodes_total || stats.stage != checkpoint::TREE);
+    }));
+    reducer.reduce().unwrap();
+
+    let stats = reducer.stats();
+    assert_eq!(stats.tokens, reducer.root.tokens());
+    assert!(stats.tokens < stats.original_tokens);
+
+    let tried: usize = stats.stages.iter().map(|(_, s)| s.tried).sum();
+    assert_eq!(tried, reports.get());
+    assert_eq!(stats.checker.runs, tried);
+    assert_eq!(stats.checker.buckets.iter().sum::<usize>(), tried);
+    assert!(stats.checker.min <= stats.checker.max);
+    for (name, stage) in &stats.stages {
+        assert!(stage.accepted <= stage.tried, "{name}");
+    }
+    assert_eq!(stats.stages[0].0, "initial");
+}
+
+#[test]
+fn report_accounts_for_all_tokens() {
+    for schedule in SCHEDULES {
+        let mut reducer = reducer(
+            "struct S<T>(u8); fn f(x: u8) -> u8 { x } fn main() { let s: S<u8> = S(f(0)); }",
+            &["structS", "lets:S", "=S"],
+        );
+        reducer.schedule = schedule;
+        let report = reducer.reduce().unwrap();
+
+        assert_eq!(report.tokens, reducer.root.tokens());
+        let removed: isize = report.strategies.iter().map(|s| s.tokens_removed).sum();
+        assert_eq!(
+            removed,
+            report.original_tokens as isize - report.tokens as isize
+        );
+
+        let tried: usize = report.strategies.iter().map(|s| s.tried).sum();
+        // only the initial test is not part of a strategy.
+        assert_eq!(tried + 1, reducer.stats().checker.runs);
+        for s in &report.strategies {
+            assert!(s.accepted <= s.tried, "{}", s.name);
+        }
+        assert!(report.strategies.iter().any(|s| s.name == "kleene/Ddmin"));
+        assert!(report.strategies.iter().any(|s| s.name == "generic-params"));
+    }
+}
+
+#[cfg(unix)]
+fn output(code: i32, stdout: &str, stderr: &str) -> std::process::Output {
+    use std::os::unix::process::ExitStatusExt;
+    std::process::Output {
+        // the raw wait status keeps the exit code in the second byte.
+        status: std::process::ExitStatus::from_raw(code << 8),
+        stdout: stdout.into(),
+        stderr: stderr.into(),
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn oracle_predicates() {
+    use crate::oracle::Predicate;
+
+    let matches = |p: &str, out| p.parse::<Predicate>().unwrap().matches(&out);
+
+    assert!(matches("exit(1)", output(1, "", "")));
+    assert!(!matches("exit(1)", output(0, "", "")));
+    assert!(matches("stdout(/a+b/)", output(0, "xaab", "")));
+    assert!(!matches("stderr(/a+b/)", output(0, "xaab", "")));
+    assert!(matches("output(/a\\/b/)", output(0, "", "a/b")));
+    assert!(matches(
+        "stderr(/E0308/) && !exit(0)",
+        output(1, "", "error[E0308]: mismatched types")
+    ));
+    assert!(matches(
+        "exit(0) || (exit(2) && !signal)",
+        output(2, "", "")
+    ));
+    assert!(!matches("signal(11)", output(0, "", "")));
+
+    let ice = "thread 'rustc' panicked at 'broken MIR in DefId(0:3)'\n\
+               error: internal compiler error: unexpected panic";
+    assert!(matches("ice", output(101, "", ice)));
+    assert!(matches("ice(/broken MIR/)", output(101, "", ice)));
+    assert!(!matches("ice(/layout/)", output(101, "", ice)));
+    assert!(!matches("ice", output(1, "", "error[E0308]")));
+
+    for invalid in [
+        "",
+        "exit",
+        "exit(x)",
+        "stdout(/a/",
+        "stdout(/(/)",
+        "ice &&",
+        "foo",
+    ] {
+        assert!(invalid.parse::<Predicate>().is_err(), "{invalid}");
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn reduce_with_oracle() {
+    use crate::oracle::Oracle;
+    use crate::Invocation;
+
+    let file = syn::parse_file("fn main() { a(); b(); c(); }").unwrap();
+    let reducer = Reducer::new(
+        file.lower(),
+        ReduceRule::Oracle(Oracle {
+            invocation: Invocation {
+                // the file name becomes `$0`.
+                args: vec!["-c".into(), "cat \"$0\"; exit 3".into()],
+                ..Invocation::new("sh")
+            },
+            predicate: "stdout(/b \\(/) && stdout(/main/) && exit(3)"
+                .parse()
+                .unwrap(),
+        }),
+    );
+    reducer.reduce().unwrap();
+
+    let s = reducer.root.to_string();
+    assert!(
+        s.contains("b (") && !s.contains("a (") && !s.contains("c ("),
+        "{s}"
+    );
+}
+
+#[test]
+fn ice_signatures() {
+    use crate::oracle::{normalize, IceSignature};
+
+    let bug = "error: internal compiler error[E0308]: mismatched types\n \
+               --> /tmp/reducedX1y2Z3.rs:3:49\n";
+    let sig = IceSignature::parse(bug).unwrap();
+    assert_eq!(sig.message, "[E0308]: mismatched types");
+    assert!(sig.queries.is_empty());
+
+    let panic = |file: &str, def: usize, query: &str| {
+        format!(
+            "thread 'rustc' (2549) panicked at compiler/rustc_mir_transform/src/lib.rs:12:5:\n\
+             broken MIR in DefId(0:{def} ~ {file}[9f3c]::main)\n\
+             stack backtrace:\n   0: rust_begin_unwind\n\
+             note: we would appreciate a bug report\n\
+             query stack during panic:\n\
+             #0 [{query}] optimizing MIR for `main`\n\
+             #1 [analysis] running analysis passes on this crate\n\
+             end of query stack\n"
+        )
+    };
+    let original = IceSignature::parse(&panic("reducedAbC123", 3, "optimized_mir")).unwrap();
+    assert_eq!(
+        original.message,
+        "compiler/rustc_mir_transform/src/lib.rs:N:N\nbroken MIR in DefId(N:N ~ FILE::main)"
+    );
+    assert_eq!(original.queries, ["optimized_mir", "analysis"]);
+
+    // the same ICE in a smaller candidate.
+    let candidate = IceSignature::parse(&panic("reducedXyZ789", 1, "optimized_mir")).unwrap();
+    assert!(original.matches(&candidate));
+    // a different query stack is a different ICE.
+    let other = IceSignature::parse(&panic("reducedXyZ789", 1, "mir_borrowck")).unwrap();
+    assert!(!original.matches(&other));
+
+    // older rustc printed the message on the same line.
+    let old = "thread 'rustc' panicked at 'called `Option::unwrap()` on a `None` value', \
+               compiler/rustc_middle/src/ty/mod.rs:42:10\n";
+    let sig = IceSignature::parse(old).unwrap();
+    assert!(sig.message.starts_with("'called `Option::unwrap()`"));
+
+    assert!(IceSignature::parse("error[E0308]: mismatched types\n").is_none());
+    assert_eq!(
+        normalize("/tmp/.tmpQ1/reducedAb1.rs:3:5 in reducedAb1[00ff]::f"),
+        "FILE.rs:N:N in FILE::f"
+    );
+}
+
+#[test]
+fn rustc_diagnostics() {
+    use crate::oracle::{diagnostics, DiagnosticPattern};
+    use regex::Regex;
+
+    let span = |primary: bool, lines: &[(&str, usize, usize)]| {
+        let text: Vec<_> = lines
+            .iter()
+            .map(|(text, start, end)| {
+                serde_json::json!({ "text": text, "highlight_start": start, "highlight_end": end })
+            })
+            .collect();
+        serde_json::json!({ "is_primary": primary, "text": text })
+    };
+    let mismatch = serde_json::json!({
+        "$message_type": "diagnostic",
+        "message": "mismatched types",
+        "code": { "code": "E0308", "explanation": "..." },
+        "level": "error",
+        "spans": [
+            span(true, &[("let s: String = héllo(a);", 17, 25)]),
+            span(false, &[("let s: String = héllo(a);", 8, 14)]),
+        ],
+        "children": [],
+    });
+    let unused = serde_json::json!({
+        "message": "unused variable: `x`",
+        "code": null,
+        "level": "warning",
+        "spans": [span(true, &[("fn f() {", 4, 9), ("    let x = 1; }", 1, 10)])],
+    });
+    let stderr = format!("{mismatch}\nnot json\n{unused}\n");
+
+    let found = diagnostics(&stderr);
+    assert_eq!(found.len(), 2);
+    assert_eq!(found[0].code.as_deref(), Some("E0308"));
+    // highlight columns count chars, not bytes.
+    assert_eq!(found[0].span_text, ["héllo(a)"]);
+    assert_eq!(found[1].code, None);
+    assert_eq!(found[1].span_text, ["f() {\n    let x"]);
+
+    let pattern =
+        |code: Option<&str>, message: Option<&str>, span: Option<&str>| DiagnosticPattern {
+            code: code.map(str::to_owned),
+            level: None,
+            message: message.map(|m| Regex::new(m).unwrap()),
+            span_text: span.map(|s| Regex::new(s).unwrap()),
+        };
+    let any = |p: DiagnosticPattern| found.iter().any(|d| p.matches(d));
+
+    assert!(any(pattern(Some("E0308"), None, None)));
+    assert!(any(pattern(
+        Some("E0308"),
+        Some("^mismatched"),
+        Some("héllo")
+    )));
+    assert!(!any(pattern(Some("E0308"), Some("unused"), None)));
+    assert!(!any(pattern(Some("E0277"), None, None)));
+    assert!(any(pattern(None, Some("unused"), Some("let x"))));
+    assert!(any(DiagnosticPattern {
+        level: Some("warning".to_owned()),
+        ..DiagnosticPattern::default()
+    }));
+}
+
+#[test]
+#[cfg(unix)]
+fn differential_oracle() {
+    use crate::oracle::{DifferentialOracle, Miri, Rustc};
+    use std::path::Path;
+    use std::process::Command;
+    use std::time::Duration;
+
+    let mut oracle = DifferentialOracle {
+        a: Rustc::default(),
+        b: Rustc {
+            flags: vec!["-Copt-level=3".into()],
+            ..Rustc::default()
+        },
+        timeout: Duration::from_secs(1),
+        miri: None,
+    };
+    let dir = tempfile::tempdir().unwrap();
+    let file = &dir.path().join("reduced.rs");
+
+    // runs of the programs compiled into out dirs `0` and `1`, `None` if it
+    // times out, and of Miri.
+    let test = |oracle: &DifferentialOracle,
+                compiles: bool,
+                runs: [Option<(i32, &str)>; 2],
+                miri: &str| {
+        oracle
+            .test(file, &mut |command: &mut Command, timeout| {
+                let program = Path::new(command.get_program());
+                let out = match program.parent().and_then(|d| d.file_name()) {
+                    Some(dir) if program.ends_with("reduced") => dir.to_str().unwrap(),
+                    _ if program.ends_with("rustup") => return Ok(Some(output(1, "", miri))),
+                    _ => {
+                        assert_eq!(timeout, None);
+                        return Ok(Some(output(if compiles { 0 } else { 1 }, "", "")));
+                    }
+                };
+                assert_eq!(timeout, Some(oracle.timeout));
+                let run = runs[out.parse::<usize>().unwrap()];
+                Ok(run.map(|(code, stdout)| output(code, stdout, "")))
+            })
+            .unwrap()
+    };
+
+    assert!(test(&oracle, true, [Some((0, "1")), Some((0, "2"))], ""));
+    assert!(test(&oracle, true, [Some((0, "1")), Some((101, "1"))], ""));
+    assert!(!test(&oracle, true, [Some((0, "1")), Some((0, "1"))], ""));
+    assert!(!test(&oracle, false, [Some((0, "1")), Some((0, "2"))], ""));
+    assert!(!test(&oracle, true, [Some((0, "1")), None], ""));
+
+    oracle.miri = Some(Miri {
+        toolchain: "nightly".to_owned(),
+        sysroot: "/sysroot".into(),
+        edition: "2021".to_owned(),
+        flags: Vec::new(),
+    });
+    let ub = "error: Undefined Behavior: out-of-bounds pointer arithmetic";
+    assert!(test(
+        &oracle,
+        true,
+        [Some((0, "1")), Some((0, "2"))],
+        "panicked"
+    ));
+    assert!(!test(&oracle, true, [Some((0, "1")), Some((0, "2"))], ub));
+
+    // the original program must terminate as well.
+    oracle.miri = None;
+    oracle.timeout = Duration::from_millis(200);
+    let err = oracle.differs("fn main() { loop {} }").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+}
+
+#[test]
+#[cfg(unix)]
+fn clippy_and_rustfmt_oracles() {
+    use crate::oracle::{
+        ClippyOracle, ClippyTarget, DiagnosticPattern, RustfmtOracle, RustfmtTarget,
+    };
+    use regex::Regex;
+    use std::fs;
+    use std::path::Path;
+    use std::process::Command;
+
+    let lint = serde_json::json!({
+        "message": "the loop variable `i` is only used to index `v`",
+        "code": { "code": "clippy::needless_range_loop", "explanation": null },
+        "level": "warning",
+        "spans": [],
+    });
+    let clippy = |lint: &str| ClippyOracle {
+        rustc: ClippyOracle::rustc(),
+        target: ClippyTarget::Lint(DiagnosticPattern {
+            code: Some(lint.to_owned()),
+            ..DiagnosticPattern::default()
+        }),
+    };
+    let stderr = format!("{lint}\n");
+    assert!(clippy("clippy::needless_range_loop").matches(&output(0, "", &stderr)));
+    assert!(!clippy("clippy::needless_borrow").matches(&output(0, "", &stderr)));
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("reduced.rs");
+    fs::write(&file, "fn main(){}").unwrap();
+    // a fake rustfmt writing `formatted` to the file after appending
+    // `suffix` for every earlier run.
+    let test = |target: RustfmtTarget, code: i32, formatted: &str, suffix: &str| {
+        let mut runs = 0;
+        RustfmtOracle::new(target)
+            .test(&file, &mut |command: &mut Command, _| {
+                let path = Path::new(command.get_args().last().unwrap());
+                assert_ne!(path, file, "the candidate is formatted in place");
+                fs::write(path, format!("{formatted}{}", suffix.repeat(runs))).unwrap();
+                runs += 1;
+                Ok(Some(output(
+                    code,
+                    "",
+                    "thread 'main' panicked at src/lib.rs",
+                )))
+            })
+            .unwrap()
+    };
+
+    assert!(test(RustfmtTarget::Crash(None), 101, "", ""));
+    assert!(!test(RustfmtTarget::Crash(None), 1, "", ""));
+    let panicked = Regex::new("panicked at src/").unwrap();
+    assert!(test(RustfmtTarget::Crash(Some(panicked)), 101, "", ""));
+    let other = Regex::new("stack overflow").unwrap();
+    assert!(!test(RustfmtTarget::Crash(Some(other)), 101, "", ""));
+
+    assert!(test(RustfmtTarget::Unstable, 0, "fn main() {}\n", " "));
+    assert!(!test(RustfmtTarget::Unstable, 0, "fn main() {}\n", ""));
+    assert!(!test(RustfmtTarget::Unstable, 1, "fn main() {}\n", " "));
+
+    let empty_main = Regex::new(r"fn main\(\) \{\}").unwrap();
+    assert!(test(
+        RustfmtTarget::Output(empty_main.clone()),
+        0,
+        "fn main() {}\n",
+        ""
+    ));
+    assert!(!test(
+        RustfmtTarget::Output(empty_main),
+        0,
+        "fn main() {\n}\n",
+        ""
+    ));
+    assert_eq!(fs::read_to_string(&file).unwrap(), "fn main(){}");
+}
+
+#[test]
+#[cfg(unix)]
+fn checker_invocation() {
+    use crate::Invocation;
+    use std::path::Path;
+
+    // each way of getting the candidate must see the same source.
+    let script = "test \"$(cat {})\" = \"$(cat \"$REDUCE_FILE\")\" && \
+                  test \"$(cat)\" = \"$(cat test.rs)\" && \
+                  grep -q 'b (' test.rs";
+    let file = syn::parse_file("fn main() { a(); b(); c(); }").unwrap();
+    let reducer = Reducer::new(
+        file.lower(),
+        ReduceRule::Program(Invocation {
+            args: vec!["-c".into(), script.into()],
+            stdin: true,
+            file_name: Some("test.rs".into()),
+            ..Invocation::new("sh")
+        }),
+    );
+    reducer.reduce().unwrap();
+    assert_eq!(reducer.root.to_string(), "fn main ( ) { b ( ) ; } ");
+
+    let command = Invocation {
+        args: vec!["--file={}".into(), "-v".into()],
+        ..Invocation::new("checker")
+    }
+    .command(Path::new("/tmp/dir/test.rs"))
+    .unwrap();
+    let args: Vec<_> = command.get_args().collect();
+    assert_eq!(args, ["--file=test.rs", "-v"]);
+    assert_eq!(command.get_current_dir(), Some(Path::new("/tmp/dir")));
+
+    // a checker reading stdin gets no file name, unless asked for it.
+    let file = tempfile::NamedTempFile::new().unwrap();
+    let name = file.path().file_name().unwrap();
+    let rustc = Invocation {
+        args: vec!["-".into()],
+        stdin: true,
+        ..Invocation::new("rustc")
+    };
+    let command = rustc.command(file.path()).unwrap();
+    assert_eq!(command.get_args().collect::<Vec<_>>(), ["-"]);
+    let command = Invocation::new("checker").command(file.path()).unwrap();
+    assert_eq!(command.get_args().collect::<Vec<_>>(), [name]);
+}
+
+#[test]
+fn scratch_dir() {
+    use std::cell::RefCell;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::rc::Rc;
+
+    let reduce = |keep: bool| {
+        let dirs = Rc::new(RefCell::new(Vec::<PathBuf>::new()));
+        let seen = dirs.clone();
+        let file = syn::parse_file("fn main() { a(); b(); c(); }").unwrap();
+        let mut reducer = Reducer::new(
+            file.lower(),
+            ReduceRule::Fn(Box::new(move |tmp| {
+                let dir = tmp.path().parent().unwrap();
+                // what the last candidate left behind is gone.
+                assert!(!dir.join("prog").exists());
+                assert_eq!(fs::read_dir(dir).unwrap().count(), 1);
+                fs::write(dir.join("prog"), "").unwrap();
+                seen.borrow_mut().push(dir.to_owned());
+                fs::read_to_string(tmp.path()).unwrap().contains("b (")
+            })),
+        );
+        reducer.keep_scratch = keep;
+        reducer.reduce().unwrap();
+
+        let dirs = dirs.take();
+        assert!(dirs.len() > 1);
+        assert!(dirs.iter().all(|d| *d == dirs[0]));
+        assert_eq!(reducer.scratch_dir().unwrap(), dirs[0]);
+        drop(reducer);
+        dirs[0].clone()
+    };
+
+    assert!(!reduce(false).exists());
+    let kept = reduce(true);
+    assert!(kept.join("prog").exists());
+    fs::remove_dir_all(kept).unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+fn log_and_replay() {
+    use crate::log;
+    use crate::Invocation;
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = syn::parse_file("fn main() { a(); b(); c(); }").unwrap();
+    let mut reducer = Reducer::new(
+        file.lower(),
+        ReduceRule::Program(Invocation {
+            args: vec!["-c".into(), "cat \"$0\"; grep -q 'b (' \"$0\"".into()],
+            ..Invocation::new("sh")
+        }),
+    );
+    reducer.log = Some(dir.path().to_owned());
+    reducer.reduce().unwrap();
+
+    let entries = log::read(dir.path()).unwrap();
+    assert_eq!(entries.len(), reducer.stats().checker.runs);
+    assert!(entries[0].interesting);
+    assert!(entries.iter().any(|e| !e.interesting));
+    for entry in &entries {
+        let source = log::source(dir.path(), entry).unwrap();
+        assert_eq!(log::hash(&source), entry.hash);
+        assert_eq!(entry.commands.len(), 1);
+        assert_eq!(entry.commands[0].stdout, source.as_bytes());
+
+        let replayed = reducer.check(&source).unwrap();
+        assert_eq!(replayed.interesting, entry.interesting);
+        assert_eq!(replayed.commands[0].status, entry.commands[0].status);
+    }
+
+    // a resumed reduction adds to the log.
+    reducer.reduce().unwrap();
+    let more = log::read(dir.path()).unwrap();
+    assert_eq!(more.len(), entries.len() + reducer.stats().checker.runs);
+    assert_eq!(more[..entries.len()], entries[..]);
+}
+
+#[test]
+#[cfg(unix)]
+fn run_kills_on_timeout_and_cancel() {
+    use crate::run::run;
+    use std::process::Command;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    let cancel = AtomicBool::new(false);
+    let finished = run(&mut Command::new("true"), None, &cancel).unwrap();
+    assert!(finished.status.unwrap().success());
+    let finished = run(Command::new("sh").args(["-c", "echo out"]), None, &cancel).unwrap();
+    assert_eq!(finished.stdout, b"out\n");
+
+    let start = Instant::now();
+    let timeout = Some(Duration::from_millis(100));
+    let finished = run(Command::new("sleep").arg("10"), timeout, &cancel).unwrap();
+    assert!(finished.status.is_none());
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    let start = Instant::now();
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            thread::sleep(Duration::from_millis(100));
+            cancel.store(true, Ordering::Relaxed);
+        });
+        let err = run(Command::new("sleep").arg("10"), None, &cancel)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::Interrupted);
+    });
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn flaky_checker() {
+    use crate::Retry;
+    use std::cell::Cell;
+    use std::fs;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    let source = "fn main() { a(); b(); c(); }";
+
+    // every other run misses an interesting candidate.
+    let calls = Rc::new(Cell::new(0));
+    let count = calls.clone();
+    let mut reducer = Reducer::new(
+        syn::parse_file(source).unwrap().lower(),
+        ReduceRule::Fn(Box::new(move |tmp| {
+            count.set(count.get() + 1);
+            count.get() % 2 == 1 && fs::read_to_string(tmp.path()).unwrap().contains("b (")
+        })),
+    );
+    reducer.retry = Retry {
+        runs: 2,
+        required: 1,
+    };
+    reducer.reduce().unwrap();
+    assert_eq!(reducer.root.to_string(), "fn main ( ) { b ( ) ; } ");
+    assert!(reducer.stats().flaky > 0);
+    assert_eq!(reducer.stats().checker.runs, calls.get());
+
+    // the first candidate without `b` is accepted once by mistake.
+    let fooled = Rc::new(Cell::new(false));
+    let once = fooled.clone();
+    let mut reducer = Reducer::new(
+        syn::parse_file(source).unwrap().lower(),
+        ReduceRule::Fn(Box::new(move |tmp| {
+            let interesting = fs::read_to_string(tmp.path()).unwrap().contains("b (");
+            interesting || !once.replace(true)
+        })),
+    );
+    reducer.verify_interval = Some(Duration::ZERO);
+    reducer.reduce().unwrap();
+    assert!(fooled.get());
+    assert_eq!(reducer.stats().rollbacks, 1);
+    assert_eq!(reducer.root.to_string(), "fn main ( ) { b ( ) ; } ");
+
+    // and then rejects the verified program once when rolling back.
+    let fooled = Rc::new(Cell::new(false));
+    let flaky = Rc::new(Cell::new(false));
+    let (once, fail) = (fooled.clone(), flaky.clone());
+    let mut reducer = Reducer::new(
+        syn::parse_file(source).unwrap().lower(),
+        ReduceRule::Fn(Box::new(move |tmp| {
+            if fs::read_to_string(tmp.path()).unwrap().contains("b (") {
+                !fail.replace(false)
+            } else {
+                let first = !once.replace(true);
+                if first {
+                    fail.set(true);
+                }
+                first
+            }
+        })),
+    );
+    reducer.verify_interval = Some(Duration::ZERO);
+    reducer.reduce().unwrap();
+    assert!(fooled.get() && !flaky.get());
+    assert_eq!(reducer.stats().rollbacks, 1);
+    assert_eq!(reducer.root.to_string(), "fn main ( ) { b ( ) ; } ");
+
+    // the checker stops accepting anything, even the verified program.
+    let calls = Rc::new(Cell::new(0));
+    let count = calls.clone();
+    let mut reducer = Reducer::new(
+        syn::parse_file(source).unwrap().lower(),
+        ReduceRule::Fn(Box::new(move |_| {
+            count.set(count.get() + 1);
+            count.get() <= 3
+        })),
+    );
+    reducer.verify_interval = Some(Duration::ZERO);
+    let err = reducer.reduce().unwrap_err();
+    assert!(err.to_string().contains("no longer interesting"), "{err}");
+    assert_eq!(reducer.stats().rollbacks, 1);
+
+    // no run would ever check the candidates.
+    for (runs, required) in [(0, 0), (2, 0), (1, 2)] {
+        let mut reducer = Reducer::new(
+            syn::parse_file(source).unwrap().lower(),
+            ReduceRule::Source(Box::new(|_| false)),
+        );
+        reducer.retry = Retry { runs, required };
+        let err = reducer.reduce().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(err.to_string().contains("retry"), "{err}");
+    }
+}
+
+#[test]
+fn uninteresting_input() {
+    let reducer = Reducer::new(
+        syn::parse_file("fn main() {}").unwrap().lower(),
+        ReduceRule::Source(Box::new(|_| false)),
+    );
+    let e = reducer.reduce().unwrap_err();
+    assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput);
+    assert_eq!(reducer.root.to_string(), "fn main ( ) { } ");
+}
+
+#[test]
+fn in_process_rules() {
+    let source = "fn main() { a(); b(); c(); }";
+
+    let reducer = Reducer::new(
+        syn::parse_file(source).unwrap().lower(),
+        ReduceRule::Source(Box::new(|s| {
+            syn::parse_file(s).is_ok() && s.contains("b (")
+        })),
+    );
+    reducer.reduce().unwrap();
+    assert_eq!(reducer.root.to_string(), "fn main ( ) { b ( ) ; } ");
+    // no file was ever written.
+    assert!(reducer.scratch.get().is_none());
+
+    // walks the tree instead of printing it.
+    fn leaves(node: &Node, out: &mut Vec<String>) {
+        let kind = node.kind();
+        let text: &str = match &*kind {
+            NodeKind::Regular { s } => s,
+            NodeKind::Temp(s) => s,
+            _ => "",
+        };
+        out.extend(text.split_whitespace().map(str::to_owned));
+        for child in node.children().iter() {
+            leaves(child, out);
+        }
+    }
+    let reducer = Reducer::new(
+        syn::parse_file(source).unwrap().lower(),
+        ReduceRule::Tree(Box::new(|node| {
+            let mut tokens = Vec::new();
+            leaves(node, &mut tokens);
+            tokens.windows(2).any(|w| w == ["c", "("])
+        })),
+    );
+    reducer.reduce().unwrap();
+    assert_eq!(reducer.root.to_string(), "fn main ( ) { c ( ) ; } ");
+    assert!(reducer.scratch.get().is_none());
+
+    // other sources are lowered to a tree of their own.
+    assert!(reducer.check("fn f() { c(); }").unwrap().interesting);
+    assert!(!reducer.check("fn f() { b(); }").unwrap().interesting);
+}
@@ -24,9 +24,9 @@ fn run_(path: PathBuf) -> Result<(), Box<dyn Error>> {
 
     let expected_output = fs::read(path.with_file_name(format!("{file_name}.output")))?;
 
-    let reducer = Reducer {
-        root: node,
-        rule: ducere::ReduceRule::Fn(Box::new(move |tmp| {
+    let reducer = Reducer::new(
+        node,
+        ducere::ReduceRule::Fn(Box::new(move |tmp| {
             let prog = tmp.path().parent().unwrap().join("prog");
 
             if !Command::new("rustc")
@@ -45,7 +45,7 @@ fn run_(path: PathBuf) -> Result<(), Box<dyn Error>> {
             let stdout = Command::new(prog).output().unwrap().stdout;
             stdout == expected_output
         })),
-    };
+    );
 
     reducer.reduce()?;
 